@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use kcl_async::{
     checkpoint::Checkpointer,
+    context::ShardContext,
     message::input::{
         InitializeMessage, LeaseLostMessage, ProcessRecordsMessage, ShardEndedMessage,
         ShutdownMessage, ShutdownRequestedMessage,
@@ -17,12 +18,17 @@ pub struct ExampleProcessor;
 impl<T: Transport + Send> Processor<T> for ExampleProcessor {
     type Error = ();
 
-    async fn initialize(&mut self, _msg: InitializeMessage) -> Result<(), Self::Error> {
+    async fn initialize(
+        &mut self,
+        _ctx: &ShardContext,
+        _msg: InitializeMessage,
+    ) -> Result<(), Self::Error> {
         Ok(())
     }
 
     async fn process_records(
         &mut self,
+        _ctx: &ShardContext,
         msg: ProcessRecordsMessage,
         checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error> {
@@ -41,6 +47,7 @@ impl<T: Transport + Send> Processor<T> for ExampleProcessor {
 
     async fn shutdown(
         &mut self,
+        _ctx: &ShardContext,
         _msg: ShutdownMessage,
         _checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error> {
@@ -49,17 +56,26 @@ impl<T: Transport + Send> Processor<T> for ExampleProcessor {
 
     async fn shutdown_requested(
         &mut self,
+        _ctx: &ShardContext,
         _msg: ShutdownRequestedMessage,
         _checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error> {
         Err(())
     }
 
-    async fn lease_lost(&mut self, _msg: LeaseLostMessage) -> Result<(), Self::Error> {
+    async fn lease_lost(
+        &mut self,
+        _ctx: &ShardContext,
+        _msg: LeaseLostMessage,
+    ) -> Result<(), Self::Error> {
         Err(())
     }
 
-    async fn shard_ended(&mut self, _msg: ShardEndedMessage) -> Result<(), Self::Error> {
+    async fn shard_ended(
+        &mut self,
+        _ctx: &ShardContext,
+        _msg: ShardEndedMessage,
+    ) -> Result<(), Self::Error> {
         Err(())
     }
 }
@@ -1,23 +1,111 @@
 use clap::Parser;
 
+use kcl_bootstrap::lock;
+use kcl_bootstrap::maven::MavenPackage;
+use kcl_bootstrap::repository::Repository;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct Options {
     #[arg(short = 'j', long = "java")]
     pub java_location: Option<String>,
 
+    /// Minimum Java major version, instead of the one the KCL requires
+    #[arg(long = "min-java")]
+    pub min_java: Option<u32>,
+
     #[arg(short = 'p', long = "properties")]
     pub properties_file: String,
 
+    /// Launch even if the properties file has errors
+    #[arg(long = "skip-validation")]
+    pub skip_validation: bool,
+
+    /// Write a starter properties file to `--properties` from the flags below and exit
+    #[arg(long = "init")]
+    pub init: bool,
+
+    #[arg(long = "executable-name", requires = "init")]
+    pub executable_name: Option<String>,
+
+    #[arg(long = "application-name", requires = "init")]
+    pub application_name: Option<String>,
+
+    #[arg(long = "stream-name", requires = "init")]
+    pub stream_name: Option<String>,
+
+    #[arg(long = "region", requires = "init")]
+    pub region_name: Option<String>,
+
+    /// `TRIM_HORIZON` (default) or `LATEST`
+    #[arg(long = "initial-position", requires = "init")]
+    pub initial_position: Option<String>,
+
     #[arg(long = "jar-folder", default_value = "jars")]
     pub jar_folder: String,
 
     #[arg(long = "pom", default_value = "pom.xml")]
     pub pom_file: String,
 
+    /// Resolve `groupId:artifactId:version` and its dependencies instead of the POM file
+    #[arg(long = "artifact")]
+    pub artifact: Option<MavenPackage>,
+
     #[arg(short = 'e', long = "execute")]
     pub should_execute: bool,
 
+    /// Replace kcl-bootstrap with Java instead of running it as a child (Unix only)
+    #[arg(long = "exec", requires = "should_execute")]
+    pub exec: bool,
+
+    /// Option for the JVM, e.g. `--jvm-option=-Xmx2g`
+    #[arg(long = "jvm-option", allow_hyphen_values = true)]
+    pub jvm_options: Vec<String>,
+
+    /// Java system property `KEY=VALUE`
+    #[arg(short = 'D', value_name = "KEY=VALUE")]
+    pub system_properties: Vec<String>,
+
+    /// Set as `JAVA_TOOL_OPTIONS` for the JVM
+    #[arg(long = "java-tool-options", allow_hyphen_values = true)]
+    pub java_tool_options: Option<String>,
+
     #[arg(short = 'l', long = "log-configuration")]
     pub logback_configuration: Option<String>,
+
+    /// Verify PGP signatures of all artifacts against this keyring (requires `gpgv`)
+    #[arg(long = "keyring")]
+    pub keyring: Option<String>,
+
+    /// Additional repository as `ID=URL` with an http(s) or file URL, tried in the given order (their priority) before Maven Central
+    #[arg(long = "repository")]
+    pub repositories: Vec<Repository>,
+
+    /// Maven settings with mirrors, server credentials and local repository [default: ~/.m2/settings.xml]
+    #[arg(long = "settings")]
+    pub settings_file: Option<String>,
+
+    /// Local Maven repository to use artifacts from first [default: ~/.m2/repository]
+    #[arg(long = "local-repository")]
+    pub local_repository: Option<String>,
+
+    /// Install exactly the artifacts recorded here, created if missing
+    #[arg(long = "lockfile", default_value = lock::DEFAULT_PATH)]
+    pub lockfile: String,
+
+    /// Always resolve the dependencies and neither read nor write a lockfile
+    #[arg(long = "no-lockfile", conflicts_with = "update_lock")]
+    pub no_lockfile: bool,
+
+    /// Resolve the dependencies again and rewrite the lockfile
+    #[arg(long = "update-lock")]
+    pub update_lock: bool,
+
+    /// Number of JARs to fetch concurrently
+    #[arg(long = "jobs", default_value_t = 4)]
+    pub jobs: usize,
+
+    /// Never access the network, only use the local repository and the JAR folder
+    #[arg(long = "offline")]
+    pub offline: bool,
 }
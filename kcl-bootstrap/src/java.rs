@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::maven::MavenPackage;
+
+/// Groups of the KCL with the Java version they require: 1.x was published
+/// under `com.amazonaws`, 2.x and later under `software.amazon.kinesis`.
+const KCL_GROUPS: [(&str, u32); 2] = [("com.amazonaws", 7), ("software.amazon.kinesis", 8)];
+const KCL_ARTIFACT: &str = "amazon-kinesis-client";
+
+#[cfg(windows)]
+const JAVA_BINARY: &str = "java.exe";
+#[cfg(not(windows))]
+const JAVA_BINARY: &str = "java";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaVersion {
+    /// `8` for `1.8.0_292`, `17` for `17.0.2`.
+    pub major: u32,
+    pub raw: String,
+}
+
+impl JavaVersion {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let mut parts = raw.split(|c: char| !c.is_ascii_digit());
+        let mut major = parts.next()?.parse().ok()?;
+
+        // Up to Java 8 versions are `1.<major>`
+        if major == 1 {
+            major = parts.next()?.parse().ok()?;
+        }
+
+        Some(Self {
+            major,
+            raw: raw.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for JavaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// The minimum Java version and what requires it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub major: u32,
+    pub reason: String,
+}
+
+impl Requirement {
+    /// What the KCL on the classpath needs: Java 7 for 1.x, Java 8 since 2.x.
+    pub fn from_packages(packages: &[MavenPackage]) -> Option<Self> {
+        packages
+            .iter()
+            .filter(|package| package.artifact_id == KCL_ARTIFACT)
+            .find_map(|package| {
+                let (group, major) = KCL_GROUPS
+                    .iter()
+                    .find(|(group, _)| package.group_id == *group)?;
+
+                Some(Self {
+                    major: *major,
+                    reason: format!("{group}:{KCL_ARTIFACT} {}", package.version),
+                })
+            })
+    }
+}
+
+/// Where a Java runtime was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub source: &'static str,
+}
+
+/// All Java runtimes found, in order of preference: `custom`, `JAVA_HOME`,
+/// `PATH` and then common install locations.
+pub fn candidates(custom: Option<&Path>) -> Vec<Candidate> {
+    let mut candidates = vec![];
+
+    if let Some(custom) = custom {
+        candidates.push(Candidate {
+            path: custom.to_path_buf(),
+            source: "custom path",
+        });
+    }
+
+    if let Some(home) = env::var_os("JAVA_HOME") {
+        candidates.push(Candidate {
+            path: Path::new(&home).join("bin").join(JAVA_BINARY),
+            source: "JAVA_HOME",
+        });
+    }
+
+    if let Ok(paths) = which::which_all(JAVA_BINARY) {
+        candidates.extend(paths.map(|path| Candidate {
+            path,
+            source: "PATH",
+        }));
+    }
+
+    for home in install_locations() {
+        candidates.push(Candidate {
+            path: home.join("bin").join(JAVA_BINARY),
+            source: "install location",
+        });
+    }
+
+    // The same runtime is usually reachable in several ways
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| {
+        candidate.path.is_file()
+            && seen.insert(fs::canonicalize(&candidate.path).unwrap_or(candidate.path.clone()))
+    });
+
+    candidates
+}
+
+/// Java homes in the usual install directories, newest first by name.
+fn install_locations() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+
+    let roots: Vec<(PathBuf, &str)> = vec![
+        (PathBuf::from("/usr/lib/jvm"), ""),
+        (PathBuf::from("/usr/java"), ""),
+        (PathBuf::from("/opt/java"), ""),
+        (home.join(".sdkman/candidates/java"), ""),
+        (
+            PathBuf::from("/Library/Java/JavaVirtualMachines"),
+            "Contents/Home",
+        ),
+        (
+            home.join("Library/Java/JavaVirtualMachines"),
+            "Contents/Home",
+        ),
+        (PathBuf::from(r"C:\Program Files\Java"), ""),
+        (PathBuf::from(r"C:\Program Files\Eclipse Adoptium"), ""),
+        (PathBuf::from(r"C:\Program Files\Amazon Corretto"), ""),
+    ];
+
+    let mut homes = vec![];
+
+    for (root, suffix) in roots {
+        let Ok(entries) = fs::read_dir(&root) else {
+            continue;
+        };
+
+        let mut found = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path().join(suffix))
+            .collect::<Vec<_>>();
+
+        found.sort();
+        homes.extend(found.into_iter().rev());
+    }
+
+    homes.extend(
+        ["/opt/homebrew/opt/openjdk", "/usr/local/opt/openjdk"]
+            .into_iter()
+            .map(PathBuf::from),
+    );
+
+    homes
+}
+
+/// Runs `java -version`.
+pub fn probe(java: &Path) -> Result<JavaVersion, Box<dyn std::error::Error>> {
+    let output = Command::new(java)
+        .arg("-version")
+        .output()
+        .map_err(|err| format!("Failed to run {}: {err}", java.display()))?;
+
+    // Printed to stderr, possibly after `Picked up JAVA_TOOL_OPTIONS: ...`
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    stderr
+        .lines()
+        .find_map(|line| {
+            let (_, rest) = line.split_once("version \"")?;
+            let (version, _) = rest.split_once('"')?;
+            JavaVersion::parse(version)
+        })
+        .ok_or_else(|| {
+            format!(
+                "Unexpected output of {} -version:\n{}",
+                java.display(),
+                stderr.trim()
+            )
+            .into()
+        })
+}
+
+/// Picks the first candidate satisfying `requirement`.
+///
+/// An explicitly given runtime is used even if its version cannot be
+/// determined, but never if it is known to be too old.
+pub fn find(
+    custom: Option<&Path>,
+    requirement: Option<&Requirement>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let too_old =
+        |version: &JavaVersion| requirement.filter(|requirement| version.major < requirement.major);
+
+    if let Some(custom) = custom {
+        eprintln!("Using custom Java path: {}", custom.display());
+
+        match probe(custom) {
+            Ok(version) => {
+                if let Some(requirement) = too_old(&version) {
+                    return Err(format!(
+                        "Java {version} at {} is too old, {} requires Java {} or later",
+                        custom.display(),
+                        requirement.reason,
+                        requirement.major
+                    )
+                    .into());
+                }
+
+                eprintln!("Java version: {version}");
+            }
+            Err(err) => eprintln!("Could not determine the Java version: {err}"),
+        }
+
+        return Ok(custom.to_path_buf());
+    }
+
+    let mut rejected = vec![];
+
+    for candidate in candidates(None) {
+        let version = match probe(&candidate.path) {
+            Ok(version) => version,
+            Err(err) => {
+                eprintln!("Skipping Java from {}: {err}", candidate.source);
+                continue;
+            }
+        };
+
+        eprintln!(
+            "Found Java {version} ({}): {}",
+            candidate.source,
+            candidate.path.display()
+        );
+
+        if let Some(requirement) = too_old(&version) {
+            eprintln!(
+                "Skipping, {} requires Java {}",
+                requirement.reason, requirement.major
+            );
+            rejected.push(format!("  {version}: {}", candidate.path.display()));
+            continue;
+        }
+
+        return Ok(candidate.path);
+    }
+
+    match requirement {
+        Some(requirement) if !rejected.is_empty() => Err(format!(
+            "No suitable Java found, {} requires Java {} or later but only found:\n{}",
+            requirement.reason,
+            requirement.major,
+            rejected.join("\n")
+        )
+        .into()),
+        _ => Err("Java not found, set JAVA_HOME, use --java or add it to PATH".into()),
+    }
+}
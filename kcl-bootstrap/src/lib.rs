@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use java::Requirement;
+use lock::Lockfile;
+use maven::MavenPackage;
+use pom::{Dependency, Pom};
+use properties::{Properties, Severity};
+use repository::{Fetched, Repositories, Repository, Settings};
+use verify::{Algorithm, Verification};
+
+pub mod java;
+pub mod lock;
+pub mod maven;
+pub mod pom;
+pub mod process;
+pub mod properties;
+pub mod repository;
+pub mod resolve;
+pub mod verify;
+pub mod xml;
+
+/// Set for the MultiLangDaemon started by [`Bootstrap::launch_self`], and so
+/// for the workers it starts.
+pub const CHILD_ENV: &str = "KCL_BOOTSTRAP_CHILD";
+
+const MULTILANG_DAEMON: &str = "software.amazon.kinesis.multilang.MultiLangDaemon";
+
+/// Whether this process is a worker started through [`Bootstrap::launch_self`].
+pub fn is_child() -> bool {
+    env::var_os(CHILD_ENV).is_some()
+}
+
+/// What to put on the classpath of the MultiLangDaemon.
+#[derive(Debug, Clone)]
+pub enum Dependencies {
+    /// The dependencies of a `pom.xml`.
+    Pom(PathBuf),
+
+    /// A single artifact, e.g. `amazon-kinesis-client-multilang`, and its
+    /// dependencies.
+    Artifact(MavenPackage),
+}
+
+/// Sets up and runs the MultiLangDaemon.
+#[derive(Debug)]
+pub struct Bootstrap {
+    pub dependencies: Dependencies,
+    pub repositories: Repositories,
+    pub verification: Verification,
+    pub jar_folder: PathBuf,
+
+    /// Installed from if it exists and created otherwise, `None` to always
+    /// resolve the dependencies.
+    pub lockfile: Option<PathBuf>,
+
+    /// Resolve again even if the lockfile exists.
+    pub update_lock: bool,
+
+    /// Number of JARs to fetch concurrently.
+    pub jobs: usize,
+
+    /// Looked up in `JAVA_HOME`, `PATH` and common install locations if not set.
+    pub java: Option<PathBuf>,
+
+    /// Overrides the Java version required by the KCL on the classpath.
+    pub min_java: Option<u32>,
+
+    /// Passed to the JVM before the classpath, e.g. `-Xmx2g` or `-Dkey=value`.
+    pub jvm_options: Vec<String>,
+
+    /// Set as `JAVA_TOOL_OPTIONS` for the JVM.
+    pub java_tool_options: Option<String>,
+    pub log_configuration: Option<PathBuf>,
+}
+
+impl Bootstrap {
+    /// Uses Maven Central with the settings from `~/.m2/settings.xml`, if any.
+    pub fn new(dependencies: Dependencies) -> Result<Self, Box<dyn std::error::Error>> {
+        let settings = match Settings::default_path().filter(|path| path.exists()) {
+            Some(path) => Settings::from_file(path)?,
+            None => Settings::default(),
+        };
+
+        let repositories = Repositories::new(vec![Repository::central()], &settings);
+        Ok(Self::with_repositories(dependencies, repositories))
+    }
+
+    pub fn with_repositories(dependencies: Dependencies, repositories: Repositories) -> Self {
+        Self {
+            dependencies,
+            repositories,
+            verification: Verification::default(),
+            jar_folder: PathBuf::from("jars"),
+            lockfile: Some(PathBuf::from(lock::DEFAULT_PATH)),
+            update_lock: false,
+            jobs: 4,
+            java: None,
+            min_java: None,
+            jvm_options: vec![],
+            java_tool_options: None,
+            log_configuration: None,
+        }
+    }
+
+    /// Resolves and fetches the JARs, the current directory comes last.
+    pub fn classpath(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        Ok(self.install()?.1)
+    }
+
+    /// The command running the MultiLangDaemon with `properties_file`.
+    ///
+    /// Fails if no Java runtime new enough for the KCL is found.
+    pub fn command(&self, properties_file: &Path) -> Result<Command, Box<dyn std::error::Error>> {
+        let (packages, classpath) = self.install()?;
+
+        let requirement = match self.min_java {
+            Some(major) => Some(Requirement {
+                major,
+                reason: "--min-java".to_string(),
+            }),
+            None => Requirement::from_packages(&packages),
+        };
+
+        eprintln!("Looking for Java...");
+        let java = java::find(self.java.as_deref(), requirement.as_ref())?;
+
+        eprintln!("Building classpath...");
+        let classpath_str = std::env::join_paths(classpath)?
+            .into_string()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Non unicode in classpath"))?;
+
+        eprintln!("Building command line...");
+        let mut cmd = Command::new(&java);
+        cmd.args(&self.jvm_options);
+        cmd.args(["-cp", &classpath_str]);
+        cmd.arg(MULTILANG_DAEMON);
+        cmd.arg("-p").arg(properties_file);
+
+        if let Some(logback) = &self.log_configuration {
+            cmd.arg("-l").arg(logback);
+        }
+
+        if let Some(options) = &self.java_tool_options {
+            cmd.env("JAVA_TOOL_OPTIONS", options);
+        }
+
+        Ok(cmd)
+    }
+
+    /// The packages on the classpath and the classpath itself.
+    fn install(&self) -> Result<(Vec<MavenPackage>, Vec<PathBuf>), Box<dyn std::error::Error>> {
+        let jar_folder = &self.jar_folder;
+        let mut fetch_pom =
+            |pkg: &MavenPackage| pkg.fetch_pom(jar_folder, &self.repositories, &self.verification);
+
+        let lockfile = match &self.lockfile {
+            Some(path) if !self.update_lock => Lockfile::load(path)?,
+            _ => None,
+        };
+
+        let pom_sha256 = match &self.dependencies {
+            Dependencies::Pom(path) => Some(Algorithm::Sha256.digest_file(path)?),
+            Dependencies::Artifact(_) => None,
+        };
+
+        // Parents and imported BOMs are only fetched if the POM changed
+        let pom_locked = pom_sha256.is_some()
+            && lockfile
+                .as_ref()
+                .is_some_and(|lockfile| lockfile.pom_sha256 == pom_sha256);
+
+        let (roots, managed) = match &self.dependencies {
+            Dependencies::Artifact(artifact) => (vec![Dependency::from_package(artifact)], vec![]),
+            Dependencies::Pom(_) if pom_locked => (vec![], vec![]),
+            Dependencies::Pom(path) => {
+                eprintln!("Parsing POM...");
+                let pom = Pom::from_file(path)?.effective(&mut fetch_pom)?;
+                (pom.dependencies, pom.dependency_management)
+            }
+        };
+
+        let (packages, fetched) = match lockfile {
+            Some(lockfile) => {
+                eprintln!("Installing from lockfile...");
+
+                if !pom_locked {
+                    lockfile.check_roots(&roots)?;
+                }
+
+                let packages = lockfile.packages()?;
+                let fetched = self.fetch_jars(&packages, &lockfile.repositories())?;
+                lockfile.verify(&fetched)?;
+                (packages, fetched)
+            }
+            None => {
+                eprintln!("Resolving dependencies...");
+                let packages = resolve::resolve(&roots, &managed, fetch_pom)?;
+
+                eprintln!("Fetching JAR files...");
+                let fetched = self.fetch_jars(&packages, &vec![None; packages.len()])?;
+
+                if let Some(path) = &self.lockfile {
+                    Lockfile::new(pom_sha256, &roots, &packages, &fetched)?.save(path)?;
+                }
+
+                (packages, fetched)
+            }
+        };
+
+        let mut classpath = fetched
+            .into_iter()
+            .map(|fetched| fetched.path)
+            .collect::<Vec<_>>();
+        classpath.push(env::current_dir()?); // Add CWD to classpath
+
+        Ok((packages, classpath))
+    }
+
+    /// Runs the MultiLangDaemon with the current executable as worker.
+    ///
+    /// The properties are copied to a temporary file with `executableName`
+    /// replaced. The daemon and its workers get [`CHILD_ENV`] set, so the
+    /// executable can tell with [`is_child`] whether to bootstrap or to work.
+    ///
+    /// Termination signals are forwarded to the daemon on Unix, and its exit
+    /// status is returned once it exited.
+    pub fn launch_self(
+        &self,
+        properties_file: impl AsRef<Path>,
+    ) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+        let executable = env::current_exe()?;
+        let executable_name = executable.to_string_lossy();
+
+        // The daemon splits the executable name into program and arguments
+        if executable_name.contains(char::is_whitespace) {
+            return Err(format!(
+                "Executable path must not contain whitespace: {}",
+                executable.display()
+            )
+            .into());
+        }
+
+        let mut properties = Properties::from_file(properties_file)?;
+        properties.set("executableName", &executable_name);
+
+        let issues = properties::validate(&properties);
+
+        for issue in &issues {
+            eprintln!("{issue}");
+        }
+
+        if issues.iter().any(|issue| issue.severity == Severity::Error) {
+            return Err("Invalid properties".into());
+        }
+
+        let path = write_temp_properties(&properties)?;
+        eprintln!("Written properties: {}", path.display());
+
+        let status = self.command(&path).and_then(|mut cmd| {
+            cmd.env(CHILD_ENV, "1");
+
+            eprintln!("Launching MultiLangDaemon with worker: {executable_name}");
+            process::run(&mut cmd)
+        });
+
+        // The daemon reads the file on startup, so it is only removed once it
+        // exited; it stays behind if this process is killed
+        let _ = fs::remove_file(&path);
+
+        status
+    }
+
+    /// `origins` are the repository URLs to download the packages from, if
+    /// known.
+    fn fetch_jars(
+        &self,
+        packages: &[MavenPackage],
+        origins: &[Option<&str>],
+    ) -> Result<Vec<Fetched>, Box<dyn std::error::Error>> {
+        fetch_jars(
+            &self.jar_folder,
+            packages,
+            origins,
+            &self.repositories,
+            &self.verification,
+            self.jobs,
+        )
+    }
+}
+
+/// Writes `properties` to a new file in the temporary directory which only the
+/// current user can read.
+///
+/// The name is random and the file must not exist yet, so it cannot be
+/// replaced by, or written through, a file or symlink created by someone else.
+fn write_temp_properties(properties: &Properties) -> io::Result<PathBuf> {
+    use std::hash::{BuildHasher as _, RandomState};
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    loop {
+        let random = RandomState::new().hash_one(Instant::now());
+        let path = env::temp_dir().join(format!(
+            "kcl-bootstrap-{}-{random:016x}.properties",
+            std::process::id()
+        ));
+
+        let mut file = match options.open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+
+        if let Err(err) = file.write_all(properties.to_string().as_bytes()) {
+            let _ = fs::remove_file(&path);
+            return Err(err);
+        }
+
+        return Ok(path);
+    }
+}
+
+fn fetch_jars(
+    jar_folder: &Path,
+    packages: &[MavenPackage],
+    origins: &[Option<&str>],
+    repositories: &Repositories,
+    verification: &Verification,
+    jobs: usize,
+) -> Result<Vec<Fetched>, Box<dyn std::error::Error>> {
+    eprintln!("Fetching JARs into folder: {}", jar_folder.display());
+    let started = Instant::now();
+
+    // Artifacts of different groups may share a file name, these go into a
+    // folder per group so concurrent fetches never write the same file
+    let mut file_names = HashMap::<_, usize>::new();
+    for pkg in packages {
+        *file_names.entry(pkg.file_name()).or_default() += 1;
+    }
+
+    let folders = packages
+        .iter()
+        .map(|pkg| match file_names[&pkg.file_name()] {
+            1 => jar_folder.to_path_buf(),
+            _ => jar_folder.join(&pkg.group_id),
+        })
+        .collect::<Vec<_>>();
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+
+    // Only the resolved JARs, stale ones from earlier versions may still be in the folder
+    let mut results = thread::scope(|scope| {
+        let workers = (0..jobs.clamp(1, packages.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(pkg) = packages.get(index) else {
+                            break;
+                        };
+
+                        let result = pkg
+                            .fetch(&folders[index], repositories, verification, origins[index])
+                            .map_err(|err| format!("{}: {err}", pkg.file_name()));
+
+                        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        eprintln!("[{done}/{}] {}", packages.len(), pkg.file_name());
+                        results.push((index, result));
+                    }
+
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Download worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    // Keep the resolution order for the classpath, not the order downloads finished in
+    results.sort_by_key(|(index, _)| *index);
+
+    let (paths, errors): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .map(|(_, result)| result)
+        .partition(Result::is_ok);
+
+    let statistics = repositories.statistics();
+    eprintln!(
+        "Fetched {} JARs in {:.1?}, downloaded {} files ({} KiB)",
+        paths.len(),
+        started.elapsed(),
+        statistics.downloaded,
+        statistics.bytes / 1024
+    );
+
+    if !errors.is_empty() {
+        let errors = errors
+            .into_iter()
+            .filter_map(Result::err)
+            .collect::<Vec<_>>();
+
+        return Err(format!(
+            "Failed to fetch {} JARs:\n{}",
+            errors.len(),
+            errors.join("\n")
+        )
+        .into());
+    }
+
+    Ok(paths.into_iter().filter_map(Result::ok).collect())
+}
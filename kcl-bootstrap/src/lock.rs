@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::maven::MavenPackage;
+use crate::pom::Dependency;
+use crate::repository::Fetched;
+use crate::verify::{Algorithm, Checksum};
+
+pub const DEFAULT_PATH: &str = "kcl-bootstrap.lock";
+
+const VERSION: u32 = 1;
+const HEADER: &str =
+    "# This file is generated by kcl-bootstrap.\n# It is not intended for manual editing.\n";
+
+/// A dependency declared in the POM (or given with `--artifact`), recorded
+/// to detect when the POM changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Root {
+    pub coordinates: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclusions: Vec<String>,
+}
+
+impl Root {
+    fn from_dependency(dependency: &Dependency) -> Self {
+        Self {
+            coordinates: dependency
+                .package()
+                .map(|package| package.to_string())
+                .unwrap_or_else(|| dependency.key()),
+            exclusions: dependency
+                .exclusions
+                .iter()
+                .map(|exclusion| format!("{}:{}", exclusion.group_id, exclusion.artifact_id))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub coordinates: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub sha256: String,
+}
+
+/// The resolved classpath, installed as is on later runs instead of resolving
+/// the POM again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+
+    /// Digest of the POM the lockfile was created from; while it matches, the
+    /// parents and imported BOMs of the POM are not needed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pom_sha256: Option<String>,
+    #[serde(rename = "root", default)]
+    pub roots: Vec<Root>,
+    #[serde(rename = "artifact", default)]
+    pub artifacts: Vec<Artifact>,
+}
+
+impl Lockfile {
+    /// `packages` and `fetched` must be in the same order.
+    pub fn new(
+        pom_sha256: Option<String>,
+        roots: &[Dependency],
+        packages: &[MavenPackage],
+        fetched: &[Fetched],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let artifacts = packages
+            .iter()
+            .zip(fetched)
+            .map(|(package, fetched)| {
+                Ok(Artifact {
+                    coordinates: package.to_string(),
+                    repository: fetched.repository.clone(),
+                    sha256: Algorithm::Sha256.digest_file(&fetched.path)?,
+                })
+            })
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+
+        Ok(Self {
+            version: VERSION,
+            pom_sha256,
+            roots: Self::roots_of(roots),
+            artifacts,
+        })
+    }
+
+    /// Returns `None` if there is no lockfile.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        eprintln!("Parsing lockfile: {}", path.display());
+        let lockfile: Self = toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| format!("Invalid lockfile {}: {err}", path.display()))?;
+
+        if lockfile.version != VERSION {
+            return Err(format!(
+                "Unsupported lockfile version {} in {}",
+                lockfile.version,
+                path.display()
+            )
+            .into());
+        }
+
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        eprintln!("Writing lockfile: {}", path.display());
+
+        fs::write(path, format!("{HEADER}\n{}", toml::to_string(self)?))?;
+        Ok(())
+    }
+
+    /// Fails if the dependencies declared in the POM are not the ones the
+    /// lockfile was created from.
+    pub fn check_roots(&self, roots: &[Dependency]) -> Result<(), Box<dyn std::error::Error>> {
+        let current = Self::roots_of(roots);
+
+        if current == self.roots {
+            return Ok(());
+        }
+
+        let added = current.iter().filter(|root| !self.roots.contains(root));
+        let removed = self.roots.iter().filter(|root| !current.contains(root));
+        let mut changes = added
+            .map(|root| format!("  + {}", root.coordinates))
+            .chain(removed.map(|root| format!("  - {}", root.coordinates)))
+            .collect::<Vec<_>>();
+
+        if changes.is_empty() {
+            // Order matters for resolution, nearest wins in declaration order
+            changes.push("  (order changed)".to_string());
+        }
+
+        Err(format!(
+            "Dependencies do not match the lockfile, run with --update-lock to resolve them again:\n{}",
+            changes.join("\n")
+        )
+        .into())
+    }
+
+    /// The URLs of the repositories the artifacts were downloaded from, in the
+    /// order of [`Lockfile::packages`].
+    pub fn repositories(&self) -> Vec<Option<&str>> {
+        self.artifacts
+            .iter()
+            .map(|artifact| artifact.repository.as_deref())
+            .collect()
+    }
+
+    pub fn packages(&self) -> Result<Vec<MavenPackage>, Box<dyn std::error::Error>> {
+        self.artifacts
+            .iter()
+            .map(|artifact| Ok(artifact.coordinates.parse()?))
+            .collect()
+    }
+
+    /// Checks the fetched files against the recorded checksums, in the order
+    /// of [`Lockfile::packages`].
+    pub fn verify(&self, fetched: &[Fetched]) -> Result<(), Box<dyn std::error::Error>> {
+        for (artifact, fetched) in self.artifacts.iter().zip(fetched) {
+            Checksum::parse(Algorithm::Sha256, &artifact.sha256)?
+                .verify(&fetched.path)
+                .map_err(|err| {
+                    format!(
+                        "{} does not match the lockfile: {err}",
+                        artifact.coordinates
+                    )
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn roots_of(roots: &[Dependency]) -> Vec<Root> {
+        roots
+            .iter()
+            .filter(|dependency| dependency.is_runtime())
+            .map(Root::from_dependency)
+            .collect()
+    }
+}
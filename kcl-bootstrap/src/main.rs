@@ -1,100 +1,158 @@
-pub mod cli;
-pub mod maven;
-pub mod xml;
+mod cli;
 
-use std::env;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use clap::Parser as _;
 
 use cli::Options;
-use maven::MavenPackage;
+use kcl_bootstrap::process;
+use kcl_bootstrap::properties::{self, Properties, Severity, Starter};
+use kcl_bootstrap::repository::{Repositories, Repository, Settings};
+use kcl_bootstrap::verify::Verification;
+use kcl_bootstrap::{Bootstrap, Dependencies};
 
-fn fetch_jars(
-    jar_folder: &Path,
-    packages: &[MavenPackage],
-) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    eprintln!("Fetching JARs into folder: {}", jar_folder.display());
-    let mut paths = vec![];
+fn init_properties(args: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(&args.properties_file);
 
-    for pkg in packages {
-        pkg.fetch(jar_folder)?;
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
     }
 
-    for entry in std::fs::read_dir(jar_folder)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().is_some_and(|ext| ext == "jar") {
-            eprintln!("Found JAR: {}", path.display());
-            paths.push(path);
-        }
+    let required = |value: &Option<String>, flag: &str| {
+        value
+            .clone()
+            .ok_or_else(|| format!("--init requires --{flag}"))
+    };
+
+    let starter = Starter {
+        executable_name: required(&args.executable_name, "executable-name")?,
+        application_name: required(&args.application_name, "application-name")?,
+        stream_name: required(&args.stream_name, "stream-name")?,
+        region_name: args.region_name.clone(),
+        initial_position: args.initial_position.clone(),
+    };
+
+    let content = properties::generate(&starter);
+    let issues = properties::validate(&Properties::parse(&content));
+
+    if let Some(issue) = issues
+        .iter()
+        .find(|issue| issue.severity == Severity::Error)
+    {
+        return Err(format!("Invalid value: {}", issue.message).into());
     }
 
-    paths.push(env::current_dir()?); // Add CWD to classpath
-    Ok(paths)
+    std::fs::write(path, content)?;
+    eprintln!("Written properties: {}", path.display());
+    Ok(())
 }
 
-fn find_java(custom: Option<String>) -> Option<String> {
-    if let Some(java) = custom {
-        eprintln!("Using custom Java path: {java}");
-        return Some(java);
+fn check_properties(args: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let issues = properties::validate(&Properties::from_file(&args.properties_file)?);
+
+    for issue in &issues {
+        eprintln!("{}: {issue}", args.properties_file);
     }
 
-    let found = which::which("java")
-        .ok()
-        .map(|p| p.to_string_lossy().to_string());
-    if let Some(ref path) = found {
-        eprintln!("Found Java in PATH: {path}");
-    } else {
-        eprintln!("Java not found in PATH");
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+
+    if errors > 0 && !args.skip_validation {
+        return Err(format!(
+            "{} has {errors} error(s), fix them or run with --skip-validation",
+            args.properties_file
+        )
+        .into());
     }
 
-    found
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("Parsing CLI arguments...");
     let args = Options::parse();
 
-    eprintln!("Parsing POM...");
-    let packages = xml::parse_pom(&args.pom_file)?;
+    if args.init {
+        return init_properties(&args);
+    }
 
-    let jar_folder = Path::new(&args.jar_folder);
-    eprintln!("Fetching JAR files...");
-    let classpath = fetch_jars(jar_folder, &packages)?;
+    check_properties(&args)?;
 
-    eprintln!("Looking for Java...");
-    let java =
-        find_java(args.java_location).ok_or("Java not found (neither custom path nor in PATH)")?;
+    let settings = match &args.settings_file {
+        Some(path) => Settings::from_file(path)?,
+        None => match Settings::default_path().filter(|path| path.exists()) {
+            Some(path) => Settings::from_file(path)?,
+            None => Settings::default(),
+        },
+    };
 
-    eprintln!("Building classpath...");
-    let classpath_str = std::env::join_paths(classpath)?
-        .to_str()
-        .expect("Non unicode in path")
-        .to_owned();
+    let mut remotes = args.repositories.clone();
+    remotes.push(Repository::central());
 
-    eprintln!("Building command line...");
-    let mut cmd = Command::new(&java);
-    cmd.args(["-cp", &classpath_str]);
-    cmd.arg("software.amazon.kinesis.multilang.MultiLangDaemon");
-    cmd.args(["-p", &args.properties_file]);
+    let mut repositories = Repositories::new(remotes, &settings);
+    repositories.offline |= args.offline;
 
-    if let Some(logback) = args.logback_configuration {
-        cmd.args(["-l", &logback]);
+    if let Some(local) = &args.local_repository {
+        repositories.local = Some(PathBuf::from(local));
     }
 
+    let dependencies = match &args.artifact {
+        Some(artifact) => Dependencies::Artifact(artifact.clone()),
+        None => Dependencies::Pom(PathBuf::from(&args.pom_file)),
+    };
+
+    let mut bootstrap = Bootstrap::with_repositories(dependencies, repositories);
+    bootstrap.verification = Verification {
+        keyring: args.keyring.as_ref().map(PathBuf::from),
+    };
+    bootstrap.jar_folder = PathBuf::from(&args.jar_folder);
+    bootstrap.lockfile = (!args.no_lockfile).then(|| PathBuf::from(&args.lockfile));
+    bootstrap.update_lock = args.update_lock;
+    bootstrap.jobs = args.jobs;
+    bootstrap.java = args.java_location.as_ref().map(PathBuf::from);
+    bootstrap.min_java = args.min_java;
+    bootstrap.jvm_options = args.jvm_options.clone();
+    bootstrap.jvm_options.extend(
+        args.system_properties
+            .iter()
+            .map(|property| format!("-D{property}")),
+    );
+    bootstrap.java_tool_options = args.java_tool_options.clone();
+    bootstrap.log_configuration = args.logback_configuration.as_ref().map(PathBuf::from);
+
+    let mut cmd = bootstrap.command(Path::new(&args.properties_file))?;
+
     if args.should_execute {
-        eprintln!("Executing Java process...");
-        let mut proc = cmd.spawn().expect("Failed to execute Java");
+        let status = if args.exec {
+            eprintln!("Replacing process with Java...");
+            process::exec(&mut cmd)?
+        } else {
+            eprintln!("Executing Java process...");
+            process::run(&mut cmd)?
+        };
+
+        if !status.success() {
+            eprintln!("Java exited with {status}");
+        }
 
-        proc.wait()?;
+        std::process::exit(process::exit_code(status));
     } else {
         let program = shell_escape::escape(cmd.get_program().to_string_lossy());
         let args = cmd
             .get_args()
             .map(|arg| shell_escape::escape(arg.to_string_lossy()));
-        let output = std::iter::once(program)
+        let env = cmd.get_envs().filter_map(|(key, value)| {
+            Some(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                shell_escape::escape(value?.to_string_lossy())
+            ))
+        });
+        let output = env
+            .map(Into::into)
+            .chain(std::iter::once(program))
             .chain(args)
             .collect::<Vec<_>>()
             .join(" ");
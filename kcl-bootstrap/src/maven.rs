@@ -1,13 +1,21 @@
-use reqwest::blocking::Client;
-use std::fs::{self, File};
-use std::io::copy;
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
+
+use crate::pom::Pom;
+use crate::repository::{Fetched, Repositories};
+use crate::verify::Verification;
 
 #[derive(Debug, Clone)]
 pub struct MavenPackage {
     pub group_id: String,
     pub artifact_id: String,
     pub version: String,
+    pub classifier: Option<String>,
+
+    /// File extension of the artifact, `jar` unless the dependency has
+    /// another `<type>`.
+    pub extension: String,
 }
 
 impl MavenPackage {
@@ -16,51 +24,137 @@ impl MavenPackage {
             group_id: group_id.to_string(),
             artifact_id: artifact_id.to_string(),
             version: version.to_string(),
+            classifier: None,
+            extension: "jar".to_string(),
+        }
+    }
+
+    /// Applies a dependency `<type>`, which may imply a classifier.
+    pub fn with_type(mut self, kind: &str) -> Self {
+        self.extension = match kind {
+            "jar" | "test-jar" | "bundle" | "ejb" | "ejb-client" | "maven-plugin" => "jar",
+            other => other,
         }
+        .to_string();
+
+        let implied = match kind {
+            "test-jar" => Some("tests"),
+            "ejb-client" => Some("client"),
+            _ => None,
+        };
+
+        if self.classifier.is_none() {
+            self.classifier = implied.map(str::to_string);
+        }
+
+        self
+    }
+
+    pub fn with_classifier(mut self, classifier: Option<&str>) -> Self {
+        self.classifier = classifier.map(str::to_string);
+        self
+    }
+
+    /// Whether the artifact belongs on the classpath; e.g. `pom` dependencies
+    /// only contribute their own dependencies.
+    pub fn is_jar(&self) -> bool {
+        self.extension == "jar"
     }
 
     pub fn file_name(&self) -> String {
-        format!("{}-{}.jar", self.artifact_id, self.version)
+        match &self.classifier {
+            Some(classifier) => format!(
+                "{}-{}-{classifier}.{}",
+                self.artifact_id, self.version, self.extension
+            ),
+            None => format!("{}-{}.{}", self.artifact_id, self.version, self.extension),
+        }
+    }
+
+    pub fn pom_file_name(&self) -> String {
+        format!("{}-{}.pom", self.artifact_id, self.version)
     }
 
-    pub fn url(&self) -> String {
+    /// Path of a file of the package relative to the repository root.
+    pub fn path(&self, file_name: &str) -> String {
         format!(
-            "https://repo1.maven.org/maven2/{}/{}/{}/{}",
+            "{}/{}/{}/{}",
             self.group_id.replace('.', "/"),
             self.artifact_id,
             self.version,
-            self.file_name()
+            file_name
         )
     }
 
-    pub fn fetch(&self, folder: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        eprintln!(
-            "Fetching package: {}:{}:{}",
-            self.group_id, self.artifact_id, self.version
-        );
-        fs::create_dir_all(folder)?;
-        let dest_path = folder.join(self.file_name());
-
-        if dest_path.exists() {
-            eprintln!("Already exists: {}", dest_path.display());
-            return Ok(());
+    /// Fetches the artifact, downloading it only from the repository with the
+    /// `origin` URL if given.
+    pub fn fetch(
+        &self,
+        folder: &Path,
+        repositories: &Repositories,
+        verification: &Verification,
+        origin: Option<&str>,
+    ) -> Result<Fetched, Box<dyn std::error::Error>> {
+        eprintln!("Fetching package: {self}");
+
+        repositories.fetch_from(&self.path(&self.file_name()), folder, verification, origin)
+    }
+
+    /// Fetches and parses the POM of the package, keeping a copy in a folder
+    /// per group in `folder` as POMs of different groups may share a name.
+    pub fn fetch_pom(
+        &self,
+        folder: &Path,
+        repositories: &Repositories,
+        verification: &Verification,
+    ) -> Result<Pom, Box<dyn std::error::Error>> {
+        let fetched = repositories.fetch(
+            &self.path(&self.pom_file_name()),
+            &folder.join(&self.group_id),
+            verification,
+        )?;
+
+        Pom::from_file(fetched.path)
+    }
+}
+
+impl fmt::Display for MavenPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.group_id, self.artifact_id)?;
+
+        match &self.classifier {
+            Some(classifier) => write!(f, ":{}:{classifier}", self.extension)?,
+            None if !self.is_jar() => write!(f, ":{}", self.extension)?,
+            None => {}
+        }
+
+        write!(f, ":{}", self.version)
+    }
+}
+
+impl FromStr for MavenPackage {
+    type Err = String;
+
+    /// Parses `groupId:artifactId[:extension[:classifier]]:version`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+
+        if !(3..=5).contains(&parts.len()) || parts.iter().any(|part| part.is_empty()) {
+            return Err(format!(
+                "expected groupId:artifactId[:extension[:classifier]]:version, found {s:?}"
+            ));
         }
 
-        eprintln!("Downloading from URL: {}", self.url());
-        let client = Client::new();
-        let resp = client
-            .get(self.url())
-            .header("User-Agent", "Mozilla/5.0")
-            .send()?;
+        let mut package = Self::new(parts[0], parts[1], parts[parts.len() - 1]);
+
+        if parts.len() >= 4 {
+            package.extension = parts[2].to_string();
+        }
 
-        if !resp.status().is_success() {
-            return Err(format!("Failed to fetch {}: {}", self.file_name(), resp.status()).into());
+        if parts.len() == 5 {
+            package.classifier = Some(parts[3].to_string());
         }
 
-        let mut out = File::create(&dest_path)?;
-        let mut content = resp;
-        copy(&mut content, &mut out)?;
-        eprintln!("Saved to: {}", dest_path.display());
-        Ok(())
+        Ok(package)
     }
 }
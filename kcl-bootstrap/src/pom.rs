@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::maven::MavenPackage;
+use crate::xml::Element;
+
+/// Nesting limit for properties referencing other properties.
+const MAX_INTERPOLATION_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Exclusion {
+    pub group_id: String,
+    pub artifact_id: String,
+}
+
+impl Exclusion {
+    /// Either part may be the wildcard `*`.
+    pub fn matches(&self, group_id: &str, artifact_id: &str) -> bool {
+        (self.group_id == "*" || self.group_id == group_id)
+            && (self.artifact_id == "*" || self.artifact_id == artifact_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: Option<String>,
+    pub scope: Option<String>,
+    pub classifier: Option<String>,
+
+    /// `<type>`, `jar` if not given.
+    pub kind: Option<String>,
+    pub optional: bool,
+    pub exclusions: Vec<Exclusion>,
+}
+
+impl Dependency {
+    pub fn from_package(package: &MavenPackage) -> Self {
+        Self {
+            group_id: package.group_id.clone(),
+            artifact_id: package.artifact_id.clone(),
+            version: Some(package.version.clone()),
+            scope: None,
+            classifier: package.classifier.clone(),
+            kind: Some(package.extension.clone()),
+            optional: false,
+            exclusions: vec![],
+        }
+    }
+
+    pub fn package(&self) -> Option<MavenPackage> {
+        self.version.as_deref().map(|version| {
+            MavenPackage::new(&self.group_id, &self.artifact_id, version)
+                .with_classifier(self.classifier.as_deref())
+                .with_type(self.kind())
+        })
+    }
+
+    pub fn kind(&self) -> &str {
+        self.kind.as_deref().unwrap_or("jar")
+    }
+
+    /// Identifies the dependency for `<dependencyManagement>` and conflict
+    /// resolution: `groupId:artifactId:type[:classifier]`.
+    pub fn key(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!(
+                "{}:{}:{}:{classifier}",
+                self.group_id,
+                self.artifact_id,
+                self.kind()
+            ),
+            None => format!("{}:{}:{}", self.group_id, self.artifact_id, self.kind()),
+        }
+    }
+
+    /// Whether the dependency is needed at runtime (`compile` or `runtime` scope).
+    pub fn is_runtime(&self) -> bool {
+        matches!(self.scope.as_deref(), None | Some("compile" | "runtime"))
+    }
+
+    /// Whether the dependency is a BOM to import into `<dependencyManagement>`.
+    pub fn is_import(&self) -> bool {
+        self.scope.as_deref() == Some("import") && self.kind() == "pom"
+    }
+
+    /// Fills in what is missing from the managed declaration.
+    pub fn manage(&mut self, managed: &Dependency) {
+        if self.version.is_none() {
+            self.version.clone_from(&managed.version);
+        }
+
+        if self.scope.is_none() {
+            self.scope.clone_from(&managed.scope);
+        }
+
+        for exclusion in &managed.exclusions {
+            if !self.exclusions.contains(exclusion) {
+                self.exclusions.push(exclusion.clone());
+            }
+        }
+    }
+
+    fn parse(element: &Element) -> Self {
+        let text = |name| element.child_text(name).map(str::to_string);
+
+        let exclusions = element
+            .child("exclusions")
+            .into_iter()
+            .flat_map(|exclusions| exclusions.children_named("exclusion"))
+            .map(|exclusion| Exclusion {
+                group_id: exclusion.child_text("groupId").unwrap_or("*").to_string(),
+                artifact_id: exclusion
+                    .child_text("artifactId")
+                    .unwrap_or("*")
+                    .to_string(),
+            })
+            .collect();
+
+        Self {
+            group_id: text("groupId").unwrap_or_default(),
+            artifact_id: text("artifactId").unwrap_or_default(),
+            version: text("version"),
+            scope: text("scope"),
+            classifier: text("classifier"),
+            kind: text("type"),
+            optional: element.child_text("optional") == Some("true"),
+            exclusions,
+        }
+    }
+}
+
+/// The parts of a `pom.xml` needed to resolve dependencies.
+///
+/// A parsed POM is taken as is, [`Pom::effective`] combines it with its
+/// parents and imported BOMs the way Maven does.
+#[derive(Debug, Clone, Default)]
+pub struct Pom {
+    pub parent: Option<MavenPackage>,
+    pub group_id: Option<String>,
+    pub artifact_id: Option<String>,
+    pub version: Option<String>,
+    pub properties: HashMap<String, String>,
+    pub dependency_management: Vec<Dependency>,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Pom {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        eprintln!("Parsing POM: {}", path.display());
+
+        let file = File::open(path)?;
+        Self::from_element(&Element::parse(BufReader::new(file))?)
+    }
+
+    pub fn parse(xml: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_element(&Element::parse(xml.as_bytes())?)
+    }
+
+    fn from_element(project: &Element) -> Result<Self, Box<dyn std::error::Error>> {
+        if project.name != "project" {
+            return Err(format!("expected <project>, found <{}>", project.name).into());
+        }
+
+        let parent = project.child("parent").and_then(|parent| {
+            Some(MavenPackage::new(
+                parent.child_text("groupId")?,
+                parent.child_text("artifactId")?,
+                parent.child_text("version")?,
+            ))
+        });
+
+        let dependencies = |element: Option<&Element>| {
+            element
+                .map(|dependencies| {
+                    dependencies
+                        .children_named("dependency")
+                        .map(Dependency::parse)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Ok(Self {
+            group_id: project
+                .child_text("groupId")
+                .map(str::to_string)
+                .or_else(|| parent.as_ref().map(|parent| parent.group_id.clone())),
+            artifact_id: project.child_text("artifactId").map(str::to_string),
+            version: project
+                .child_text("version")
+                .map(str::to_string)
+                .or_else(|| parent.as_ref().map(|parent| parent.version.clone())),
+            parent,
+            properties: project
+                .child("properties")
+                .map(|properties| {
+                    properties
+                        .children
+                        .iter()
+                        .map(|property| (property.name.clone(), property.text.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            dependency_management: dependencies(
+                project
+                    .child("dependencyManagement")
+                    .and_then(|management| management.child("dependencies")),
+            ),
+            dependencies: dependencies(project.child("dependencies")),
+        })
+    }
+
+    /// Builds the effective POM: inherits from the parent POMs, replaces
+    /// property references, imports BOMs and applies the dependency
+    /// management to the dependencies.
+    pub fn effective(
+        self,
+        fetch_pom: &mut impl FnMut(&MavenPackage) -> Result<Pom, Box<dyn std::error::Error>>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.effective_in(fetch_pom, &mut vec![])
+    }
+
+    /// `chain` holds the POMs currently being built to detect cycles.
+    fn effective_in(
+        self,
+        fetch_pom: &mut impl FnMut(&MavenPackage) -> Result<Pom, Box<dyn std::error::Error>>,
+        chain: &mut Vec<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Inheritance happens before interpolation, so properties of the child
+        // apply to whatever it inherits
+        let mut pom = self.inherit(fetch_pom, chain)?;
+        pom.interpolate_all();
+
+        let mut imported = vec![];
+
+        for dependency in &pom.dependency_management {
+            if !dependency.is_import() {
+                continue;
+            }
+
+            let Some(bom) = dependency.package() else {
+                eprintln!("Skipping BOM without version: {}", dependency.key());
+                continue;
+            };
+
+            let bom = Self::enter(chain, &bom, |chain| {
+                fetch_pom(&bom)?.effective_in(fetch_pom, chain)
+            })?;
+
+            imported.extend(bom.dependency_management);
+        }
+
+        pom.dependency_management
+            .retain(|dependency| !dependency.is_import());
+
+        // Declarations in the POM itself take precedence over imported ones
+        for dependency in imported {
+            pom.add_managed(dependency);
+        }
+
+        let managed = pom
+            .dependency_management
+            .iter()
+            .map(|dependency| (dependency.key(), dependency.clone()))
+            .collect::<HashMap<_, _>>();
+
+        for dependency in &mut pom.dependencies {
+            if let Some(managed) = managed.get(&dependency.key()) {
+                dependency.manage(managed);
+            }
+        }
+
+        Ok(pom)
+    }
+
+    /// Merges the parent POMs into this one, without interpolating anything
+    /// but the parent coordinates.
+    fn inherit(
+        mut self,
+        fetch_pom: &mut impl FnMut(&MavenPackage) -> Result<Pom, Box<dyn std::error::Error>>,
+        chain: &mut Vec<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(parent) = &self.parent else {
+            return Ok(self);
+        };
+
+        // E.g. `${revision}`, which can only come from this POM itself
+        let parent = MavenPackage::new(
+            &self.interpolate(&parent.group_id),
+            &self.interpolate(&parent.artifact_id),
+            &self.interpolate(&parent.version),
+        );
+        self.parent = Some(parent.clone());
+
+        let parent = Self::enter(chain, &parent, |chain| {
+            fetch_pom(&parent)?.inherit(fetch_pom, chain)
+        })?;
+
+        for (name, value) in parent.properties {
+            self.properties.entry(name).or_insert(value);
+        }
+
+        for dependency in parent.dependency_management {
+            self.add_managed(dependency);
+        }
+
+        for dependency in parent.dependencies {
+            if !self
+                .dependencies
+                .iter()
+                .any(|existing| existing.key() == dependency.key())
+            {
+                self.dependencies.push(dependency);
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn add_managed(&mut self, dependency: Dependency) {
+        if !self
+            .dependency_management
+            .iter()
+            .any(|existing| existing.key() == dependency.key())
+        {
+            self.dependency_management.push(dependency);
+        }
+    }
+
+    fn enter<T>(
+        chain: &mut Vec<String>,
+        package: &MavenPackage,
+        f: impl FnOnce(&mut Vec<String>) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let coordinates = package.to_string();
+
+        if chain.contains(&coordinates) {
+            return Err(format!(
+                "Cycle in parent POMs or BOM imports: {} -> {coordinates}",
+                chain.join(" -> ")
+            )
+            .into());
+        }
+
+        chain.push(coordinates);
+        let result = f(chain);
+        chain.pop();
+
+        result
+    }
+
+    fn interpolate_all(&mut self) {
+        let interpolate =
+            |field: &Option<String>| field.as_deref().map(|value| self.interpolate(value));
+        (self.group_id, self.artifact_id, self.version) = (
+            interpolate(&self.group_id),
+            interpolate(&self.artifact_id),
+            interpolate(&self.version),
+        );
+
+        let mut dependencies = std::mem::take(&mut self.dependencies);
+        let mut management = std::mem::take(&mut self.dependency_management);
+
+        for dependency in dependencies.iter_mut().chain(management.iter_mut()) {
+            dependency.group_id = self.interpolate(&dependency.group_id);
+            dependency.artifact_id = self.interpolate(&dependency.artifact_id);
+
+            for value in [
+                &mut dependency.version,
+                &mut dependency.scope,
+                &mut dependency.classifier,
+                &mut dependency.kind,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *value = self.interpolate(value);
+            }
+        }
+
+        self.dependencies = dependencies;
+        self.dependency_management = management;
+    }
+
+    /// Replaces `${...}` references to properties and project coordinates,
+    /// including references within property values.
+    ///
+    /// Unknown references are left untouched.
+    pub fn interpolate(&self, value: &str) -> String {
+        self.interpolate_at(value, 0)
+    }
+
+    fn interpolate_at(&self, value: &str, depth: usize) -> String {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+
+            let name = &rest[start + 2..start + end];
+            result.push_str(&rest[..start]);
+
+            match self.lookup(name) {
+                Some(resolved) if depth < MAX_INTERPOLATION_DEPTH => {
+                    result.push_str(&self.interpolate_at(resolved, depth + 1));
+                }
+                _ => result.push_str(&rest[start..=start + end]),
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        result.push_str(rest);
+        result
+    }
+
+    fn lookup(&self, name: &str) -> Option<&str> {
+        let parent = self.parent.as_ref();
+
+        match name {
+            "project.groupId" | "pom.groupId" => self.group_id.as_deref(),
+            "project.artifactId" | "pom.artifactId" => self.artifact_id.as_deref(),
+            "project.version" | "pom.version" => self.version.as_deref(),
+            "project.parent.groupId" => parent.map(|parent| parent.group_id.as_str()),
+            "project.parent.artifactId" => parent.map(|parent| parent.artifact_id.as_str()),
+            "project.parent.version" => parent.map(|parent| parent.version.as_str()),
+            _ => self.properties.get(name).map(String::as_str),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves the given POMs by `groupId:artifactId:version`.
+    fn repository<'a>(
+        poms: &'a [(&str, &str)],
+    ) -> impl FnMut(&MavenPackage) -> Result<Pom, Box<dyn std::error::Error>> + 'a {
+        |package| {
+            let coordinates = format!(
+                "{}:{}:{}",
+                package.group_id, package.artifact_id, package.version
+            );
+
+            match poms.iter().find(|(key, _)| *key == coordinates) {
+                Some((_, xml)) => Pom::parse(xml),
+                None => Err(format!("unknown POM {coordinates}").into()),
+            }
+        }
+    }
+
+    fn find<'a>(dependencies: &'a [Dependency], artifact_id: &str) -> &'a Dependency {
+        dependencies
+            .iter()
+            .find(|dependency| dependency.artifact_id == artifact_id)
+            .unwrap_or_else(|| panic!("{artifact_id} missing"))
+    }
+
+    #[test]
+    fn parses_dependencies() {
+        let pom = Pom::parse(
+            r#"<project>
+                <groupId>g</groupId><artifactId>app</artifactId><version>1</version>
+                <dependencies>
+                    <dependency>
+                        <groupId>g</groupId><artifactId>lib</artifactId><version>2</version>
+                        <scope>test</scope><classifier>tests</classifier><type>test-jar</type>
+                        <optional>true</optional>
+                        <exclusions>
+                            <exclusion><groupId>x</groupId><artifactId>y</artifactId></exclusion>
+                            <exclusion><groupId>z</groupId></exclusion>
+                        </exclusions>
+                    </dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap();
+
+        let lib = find(&pom.dependencies, "lib");
+        assert_eq!(lib.version.as_deref(), Some("2"));
+        assert_eq!(lib.scope.as_deref(), Some("test"));
+        assert_eq!(lib.key(), "g:lib:test-jar:tests");
+        assert!(lib.optional);
+        assert!(!lib.is_runtime());
+        assert!(lib.exclusions[0].matches("x", "y"));
+        assert!(!lib.exclusions[0].matches("x", "other"));
+        assert!(lib.exclusions[1].matches("z", "anything"));
+
+        assert!(Pom::parse("<settings/>").is_err());
+    }
+
+    #[test]
+    fn interpolates_properties() {
+        let pom = Pom::parse(
+            r#"<project>
+                <groupId>g</groupId><artifactId>app</artifactId><version>1.0</version>
+                <properties>
+                    <major>2</major>
+                    <lib.version>${major}.1</lib.version>
+                    <a>${b}</a>
+                    <b>${a}</b>
+                </properties>
+            </project>"#,
+        )
+        .unwrap();
+
+        assert_eq!(pom.interpolate("${lib.version}"), "2.1");
+        assert_eq!(
+            pom.interpolate("v${project.version}-${pom.groupId}"),
+            "v1.0-g"
+        );
+        assert_eq!(pom.interpolate("${unknown} and ${"), "${unknown} and ${");
+
+        // Cycles stop at the nesting limit
+        assert!(pom.interpolate("${a}").starts_with("${"));
+    }
+
+    #[test]
+    fn inherits_from_parent() {
+        let poms = [(
+            "g:parent:1",
+            r#"<project>
+                <groupId>g</groupId><artifactId>parent</artifactId><version>1</version>
+                <properties><lib.version>1.0</lib.version><other>parent</other></properties>
+                <dependencyManagement><dependencies>
+                    <dependency><groupId>g</groupId><artifactId>lib</artifactId><version>${lib.version}</version></dependency>
+                    <dependency><groupId>g</groupId><artifactId>other</artifactId><version>${other}</version></dependency>
+                </dependencies></dependencyManagement>
+                <dependencies>
+                    <dependency><groupId>g</groupId><artifactId>common</artifactId><version>3</version></dependency>
+                </dependencies>
+            </project>"#,
+        )];
+
+        let pom = Pom::parse(
+            r#"<project>
+                <parent><groupId>g</groupId><artifactId>parent</artifactId><version>1</version></parent>
+                <artifactId>child</artifactId>
+                <properties><lib.version>2.0</lib.version></properties>
+                <dependencies>
+                    <dependency><groupId>g</groupId><artifactId>lib</artifactId></dependency>
+                    <dependency><groupId>g</groupId><artifactId>other</artifactId></dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap()
+        .effective(&mut repository(&poms))
+        .unwrap();
+
+        assert_eq!(pom.group_id.as_deref(), Some("g"));
+        assert_eq!(pom.version.as_deref(), Some("1"));
+
+        // Properties of the child apply to what it inherits
+        assert_eq!(
+            find(&pom.dependencies, "lib").version.as_deref(),
+            Some("2.0")
+        );
+        assert_eq!(
+            find(&pom.dependencies, "other").version.as_deref(),
+            Some("parent")
+        );
+        assert_eq!(
+            find(&pom.dependencies, "common").version.as_deref(),
+            Some("3")
+        );
+    }
+
+    #[test]
+    fn interpolates_parent_coordinates() {
+        let poms = [(
+            "g:parent:1.2",
+            r#"<project>
+                <groupId>g</groupId><artifactId>parent</artifactId><version>1.2</version>
+                <dependencies>
+                    <dependency><groupId>g</groupId><artifactId>lib</artifactId><version>${project.version}</version></dependency>
+                </dependencies>
+            </project>"#,
+        )];
+
+        let pom = Pom::parse(
+            r#"<project>
+                <parent><groupId>g</groupId><artifactId>parent</artifactId><version>${revision}</version></parent>
+                <artifactId>child</artifactId>
+                <properties><revision>1.2</revision></properties>
+            </project>"#,
+        )
+        .unwrap()
+        .effective(&mut repository(&poms))
+        .unwrap();
+
+        assert_eq!(
+            pom.parent.as_ref().map(|parent| parent.version.as_str()),
+            Some("1.2")
+        );
+        assert_eq!(pom.version.as_deref(), Some("1.2"));
+        assert_eq!(
+            find(&pom.dependencies, "lib").version.as_deref(),
+            Some("1.2")
+        );
+    }
+
+    #[test]
+    fn imports_boms() {
+        let poms = [
+            (
+                "g:bom:1",
+                r#"<project>
+                    <groupId>g</groupId><artifactId>bom</artifactId><version>1</version>
+                    <dependencyManagement><dependencies>
+                        <dependency><groupId>g</groupId><artifactId>a</artifactId><version>1.0</version></dependency>
+                        <dependency><groupId>g</groupId><artifactId>b</artifactId><version>1.0</version><scope>runtime</scope></dependency>
+                    </dependencies></dependencyManagement>
+                </project>"#,
+            ),
+            (
+                "g:nested:1",
+                r#"<project>
+                    <groupId>g</groupId><artifactId>nested</artifactId><version>1</version>
+                    <dependencyManagement><dependencies>
+                        <dependency><groupId>g</groupId><artifactId>bom</artifactId><version>1</version><type>pom</type><scope>import</scope></dependency>
+                    </dependencies></dependencyManagement>
+                </project>"#,
+            ),
+        ];
+
+        let pom = Pom::parse(
+            r#"<project>
+                <groupId>g</groupId><artifactId>app</artifactId><version>1</version>
+                <dependencyManagement><dependencies>
+                    <dependency><groupId>g</groupId><artifactId>nested</artifactId><version>1</version><type>pom</type><scope>import</scope></dependency>
+                    <dependency><groupId>g</groupId><artifactId>b</artifactId><version>2.0</version></dependency>
+                </dependencies></dependencyManagement>
+                <dependencies>
+                    <dependency><groupId>g</groupId><artifactId>a</artifactId></dependency>
+                    <dependency><groupId>g</groupId><artifactId>b</artifactId></dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap()
+        .effective(&mut repository(&poms))
+        .unwrap();
+
+        assert!(
+            pom.dependency_management
+                .iter()
+                .all(|dependency| !dependency.is_import())
+        );
+        assert_eq!(find(&pom.dependencies, "a").version.as_deref(), Some("1.0"));
+
+        // Declarations of the POM itself win over imported ones
+        let b = find(&pom.dependencies, "b");
+        assert_eq!(b.version.as_deref(), Some("2.0"));
+        assert_eq!(b.scope, None);
+    }
+
+    #[test]
+    fn manages_version_scope_and_exclusions() {
+        let pom = Pom::parse(
+            r#"<project>
+                <groupId>g</groupId><artifactId>app</artifactId><version>1</version>
+                <dependencyManagement><dependencies>
+                    <dependency>
+                        <groupId>g</groupId><artifactId>lib</artifactId><version>1.0</version><scope>provided</scope>
+                        <exclusions><exclusion><groupId>x</groupId><artifactId>y</artifactId></exclusion></exclusions>
+                    </dependency>
+                    <dependency><groupId>g</groupId><artifactId>pinned</artifactId><version>1.0</version></dependency>
+                </dependencies></dependencyManagement>
+                <dependencies>
+                    <dependency><groupId>g</groupId><artifactId>lib</artifactId></dependency>
+                    <dependency><groupId>g</groupId><artifactId>pinned</artifactId><version>2.0</version><scope>runtime</scope></dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap()
+        .effective(&mut repository(&[]))
+        .unwrap();
+
+        let lib = find(&pom.dependencies, "lib");
+        assert_eq!(lib.version.as_deref(), Some("1.0"));
+        assert_eq!(lib.scope.as_deref(), Some("provided"));
+        assert_eq!(lib.exclusions.len(), 1);
+
+        // Explicit values are kept
+        let pinned = find(&pom.dependencies, "pinned");
+        assert_eq!(pinned.version.as_deref(), Some("2.0"));
+        assert_eq!(pinned.scope.as_deref(), Some("runtime"));
+    }
+
+    #[test]
+    fn detects_parent_cycles() {
+        let poms = [
+            (
+                "g:a:1",
+                r#"<project><parent><groupId>g</groupId><artifactId>b</artifactId><version>1</version></parent><artifactId>a</artifactId></project>"#,
+            ),
+            (
+                "g:b:1",
+                r#"<project><parent><groupId>g</groupId><artifactId>a</artifactId><version>1</version></parent><artifactId>b</artifactId></project>"#,
+            ),
+        ];
+
+        let err = Pom::parse(poms[0].1)
+            .unwrap()
+            .effective(&mut repository(&poms))
+            .unwrap_err();
+
+        assert!(err.to_string().contains("Cycle"), "{err}");
+    }
+}
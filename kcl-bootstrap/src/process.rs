@@ -0,0 +1,84 @@
+use std::process::{Command, ExitStatus};
+
+/// Signals passed on to the child, e.g. from `docker stop` or Ctrl+C.
+#[cfg(unix)]
+const FORWARDED_SIGNALS: [i32; 3] = [
+    signal_hook::consts::SIGINT,
+    signal_hook::consts::SIGTERM,
+    signal_hook::consts::SIGHUP,
+];
+
+/// Runs `cmd` until it exits, forwarding termination signals to it on Unix.
+pub fn run(cmd: &mut Command) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    let mut signals = signal_hook::iterator::Signals::new(FORWARDED_SIGNALS)?;
+
+    let mut child = cmd.spawn().map_err(|err| {
+        format!(
+            "Failed to run {}: {err}",
+            cmd.get_program().to_string_lossy()
+        )
+    })?;
+
+    #[cfg(unix)]
+    {
+        let pid = child.id() as libc::pid_t;
+        let handle = signals.handle();
+
+        let forwarder = std::thread::spawn(move || {
+            for signal in signals.forever() {
+                // SAFETY: Only sends a signal, the child is not reaped before
+                // the forwarder is stopped below so the pid cannot be reused
+                unsafe { libc::kill(pid, signal) };
+            }
+        });
+
+        let status = child.wait();
+
+        handle.close();
+        forwarder.join().expect("Signal forwarder panicked");
+
+        Ok(status?)
+    }
+
+    #[cfg(not(unix))]
+    Ok(child.wait()?)
+}
+
+/// Replaces the current process with `cmd` on Unix, so it receives signals
+/// directly, and only returns on errors. Elsewhere it is [`run`].
+pub fn exec(cmd: &mut Command) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt as _;
+
+        let err = cmd.exec();
+        Err(format!(
+            "Failed to run {}: {err}",
+            cmd.get_program().to_string_lossy()
+        )
+        .into())
+    }
+
+    #[cfg(not(unix))]
+    run(cmd)
+}
+
+/// The exit code to exit with for `status`, `128 + signal` like a shell if the
+/// child was killed.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt as _;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
@@ -0,0 +1,704 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+
+    /// 1-based line the entry starts on.
+    pub line: usize,
+}
+
+/// A Java `.properties` file.
+#[derive(Debug, Clone, Default)]
+pub struct Properties {
+    pub entries: Vec<Entry>,
+}
+
+impl Properties {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        eprintln!("Parsing properties: {}", path.display());
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+
+        Ok(Self::parse(&content))
+    }
+
+    /// Follows `java.util.Properties::load`: `#` and `!` comments, `=`, `:`
+    /// or whitespace as separator, `\` line continuations and escapes.
+    pub fn parse(input: &str) -> Self {
+        let mut entries = vec![];
+        let mut lines = input.lines().enumerate();
+
+        while let Some((index, line)) = lines.next() {
+            let mut logical = line.trim_start().to_string();
+
+            if logical.is_empty() || logical.starts_with('#') || logical.starts_with('!') {
+                continue;
+            }
+
+            while ends_with_continuation(&logical) {
+                logical.pop();
+
+                match lines.next() {
+                    Some((_, next)) => logical.push_str(next.trim_start()),
+                    None => break,
+                }
+            }
+
+            let (key, value) = split_entry(&logical);
+
+            entries.push(Entry {
+                key: unescape(key),
+                value: unescape(value),
+                line: index + 1,
+            });
+        }
+
+        Self { entries }
+    }
+
+    /// The last value wins, like in Java.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Replaces all values of `key`.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = self
+            .entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.line)
+            .unwrap_or_default();
+
+        self.entries.retain(|entry| entry.key != key);
+        self.entries.push(Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            line,
+        });
+    }
+}
+
+/// Writes the entries without comments.
+impl fmt::Display for Properties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{} = {}", escape_key(&entry.key), escape(&entry.value))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn ends_with_continuation(line: &str) -> bool {
+    line.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+/// Splits at the first unescaped separator.
+fn split_entry(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..index], line[index + 1..].trim_start()),
+            c if c.is_whitespace() => {
+                let rest = line[index..].trim_start();
+                let rest = rest
+                    .strip_prefix(['=', ':'])
+                    .map(str::trim_start)
+                    .unwrap_or(rest);
+
+                return (&line[..index], rest);
+            }
+            _ => {}
+        }
+    }
+
+    (line, "")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => match unescape_unicode(&mut chars) {
+                Ok(c) => result.push(c),
+                Err(hex) => {
+                    result.push_str("\\u");
+                    result.push_str(&hex);
+                }
+            },
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+
+    result
+}
+
+/// Decodes the hex digits following `\u`, together with the escape after it
+/// if they form a surrogate pair. Returns the digits if they are invalid.
+fn unescape_unicode(chars: &mut std::str::Chars<'_>) -> Result<char, String> {
+    let hex = chars.by_ref().take(4).collect::<String>();
+    let unit = parse_unit(&hex).ok_or_else(|| hex.clone())?;
+
+    let mut ahead = chars.clone();
+    let low = match (ahead.next(), ahead.next()) {
+        (Some('\\'), Some('u')) => parse_unit(&ahead.by_ref().take(4).collect::<String>()),
+        _ => None,
+    };
+
+    match char::decode_utf16([unit].into_iter().chain(low)).next() {
+        Some(Ok(c)) => {
+            if c.len_utf16() == 2 {
+                *chars = ahead;
+            }
+
+            Ok(c)
+        }
+        _ => Err(hex),
+    }
+}
+
+fn parse_unit(hex: &str) -> Option<u16> {
+    if hex.len() != 4 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u16::from_str_radix(hex, 16).ok()
+}
+
+fn escape_key(key: &str) -> String {
+    escape_with(key, |_, c| matches!(c, ' ' | '=' | ':' | '#' | '!'))
+}
+
+fn escape(value: &str) -> String {
+    escape_with(value, |index, c| index == 0 && c == ' ')
+}
+
+fn escape_with(value: &str, needs_backslash: impl Fn(usize, char) -> bool) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{c}' => result.push_str("\\f"),
+            // Java reads properties files as ISO 8859-1
+            c if !(' '..='~').contains(&c) => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    result.push_str(&format!("\\u{unit:04X}"));
+                }
+            }
+            c if needs_backslash(index, c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Integer,
+    Decimal,
+    Boolean,
+    Choice(&'static [&'static str]),
+}
+
+impl Kind {
+    fn check(&self, value: &str) -> Result<(), String> {
+        match self {
+            Kind::Integer if value.parse::<i64>().is_err() => {
+                Err(format!("expected an integer, found {value:?}"))
+            }
+            Kind::Decimal if value.parse::<f64>().is_err() => {
+                Err(format!("expected a number, found {value:?}"))
+            }
+            Kind::Boolean
+                if !value.eq_ignore_ascii_case("true") && !value.eq_ignore_ascii_case("false") =>
+            {
+                Err(format!("expected true or false, found {value:?}"))
+            }
+            Kind::Choice(choices) if !choices.contains(&value) => Err(format!(
+                "expected one of {}, found {value:?}",
+                choices.join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub required: bool,
+}
+
+const fn key(name: &'static str, kind: Kind) -> Key {
+    Key {
+        name,
+        kind,
+        required: false,
+    }
+}
+
+const fn required(name: &'static str, kind: Kind) -> Key {
+    Key {
+        name,
+        kind,
+        required: true,
+    }
+}
+
+const INITIAL_POSITIONS: &[&str] = &["TRIM_HORIZON", "LATEST", "AT_TIMESTAMP"];
+const BILLING_MODES: &[&str] = &["PAY_PER_REQUEST", "PROVISIONED"];
+
+/// Documented configuration of the MultiLangDaemon, see
+/// <https://github.com/awslabs/amazon-kinesis-client/blob/master/docs/kcl-configurations.md>.
+pub const KEYS: &[Key] = &[
+    required("executableName", Kind::Text),
+    required("applicationName", Kind::Text),
+    key("streamName", Kind::Text),
+    key("streamArn", Kind::Text),
+    key("regionName", Kind::Text),
+    key("processingLanguage", Kind::Text),
+    key("initialPositionInStream", Kind::Choice(INITIAL_POSITIONS)),
+    key("initialPositionInStreamExtended", Kind::Integer),
+    key("AwsCredentialsProvider", Kind::Text),
+    key("AwsCredentialsProviderDynamoDB", Kind::Text),
+    key("AwsCredentialsProviderCloudWatch", Kind::Text),
+    key("workerId", Kind::Text),
+    key("tableName", Kind::Text),
+    key("kinesisEndpoint", Kind::Text),
+    key("dynamoDBEndpoint", Kind::Text),
+    key("failoverTimeMillis", Kind::Integer),
+    key("shardSyncIntervalMillis", Kind::Integer),
+    key("maxRecords", Kind::Integer),
+    key("idleTimeBetweenReadsInMillis", Kind::Integer),
+    key("callProcessRecordsEvenForEmptyRecordList", Kind::Boolean),
+    key("parentShardPollIntervalMillis", Kind::Integer),
+    key("cleanupLeasesUponShardCompletion", Kind::Boolean),
+    key("ignoreUnexpectedChildShards", Kind::Boolean),
+    key("taskBackoffTimeMillis", Kind::Integer),
+    key("metricsBufferTimeMillis", Kind::Integer),
+    key("metricsMaxQueueSize", Kind::Integer),
+    key(
+        "metricsLevel",
+        Kind::Choice(&["NONE", "SUMMARY", "DETAILED"]),
+    ),
+    key("metricsEnabledDimensions", Kind::Text),
+    key("validateSequenceNumberBeforeCheckpointing", Kind::Boolean),
+    key("maxActiveThreads", Kind::Integer),
+    key("maxLeasesForWorker", Kind::Integer),
+    key("maxLeasesToStealAtOneTime", Kind::Integer),
+    key("initialLeaseTableReadCapacity", Kind::Integer),
+    key("initialLeaseTableWriteCapacity", Kind::Integer),
+    key("maxLeaseRenewalThreads", Kind::Integer),
+    key("listShardsBackoffTimeInMillis", Kind::Integer),
+    key("maxListShardsRetryAttempts", Kind::Integer),
+    key("skipShardSyncAtStartupIfLeasesExist", Kind::Boolean),
+    key("timeoutInSeconds", Kind::Integer),
+    key("retryGetRecordsInSeconds", Kind::Integer),
+    key("maxGetRecordsThreadPool", Kind::Integer),
+    key(
+        "clientVersionConfig",
+        Kind::Choice(&[
+            "CLIENT_VERSION_CONFIG_3X",
+            "CLIENT_VERSION_CONFIG_COMPATIBLE_WITH_2X",
+        ]),
+    ),
+    key("coordinatorStateTableName", Kind::Text),
+    key("coordinatorStateBillingMode", Kind::Choice(BILLING_MODES)),
+    key("coordinatorStateReadCapacity", Kind::Integer),
+    key("coordinatorStateWriteCapacity", Kind::Integer),
+    key("coordinatorStatePointInTimeRecoveryEnabled", Kind::Boolean),
+    key("coordinatorStateDeletionProtectionEnabled", Kind::Boolean),
+    key("coordinatorStateTags", Kind::Text),
+    key("gracefulLeaseHandoffTimeoutMillis", Kind::Integer),
+    key("isGracefulLeaseHandoffEnabled", Kind::Boolean),
+    key("workerMetricsTableName", Kind::Text),
+    key("workerMetricsBillingMode", Kind::Choice(BILLING_MODES)),
+    key("workerMetricsReadCapacity", Kind::Integer),
+    key("workerMetricsWriteCapacity", Kind::Integer),
+    key("workerMetricsPointInTimeRecoveryEnabled", Kind::Boolean),
+    key("workerMetricsDeletionProtectionEnabled", Kind::Boolean),
+    key("workerMetricsTags", Kind::Text),
+    key("inMemoryWorkerMetricsCaptureFrequencyMillis", Kind::Integer),
+    key("workerMetricsReporterFreqInMillis", Kind::Integer),
+    key("noOfPersistedMetricsPerWorkerMetrics", Kind::Integer),
+    key("disableWorkerMetrics", Kind::Boolean),
+    key("maxThroughputPerHostKBps", Kind::Decimal),
+    key("dampeningPercentage", Kind::Integer),
+    key("reBalanceThresholdPercentage", Kind::Integer),
+    key("allowThroughputOvershoot", Kind::Boolean),
+    key("varianceBalancingFrequency", Kind::Integer),
+    key("workerMetricsEMAAlpha", Kind::Decimal),
+    key("staleWorkerMetricsEntryCleanupDuration", Kind::Text),
+    key("leaseAssignmentIntervalMillis", Kind::Integer),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        match self.line {
+            Some(line) => write!(f, "{severity}: line {line}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// Checks the keys and values against [`KEYS`].
+///
+/// Unknown keys are only warnings, as the KCL accepts more options than are
+/// documented.
+pub fn validate(properties: &Properties) -> Vec<Issue> {
+    let known = KEYS
+        .iter()
+        .map(|key| (key.name, key))
+        .collect::<HashMap<_, _>>();
+
+    let mut issues = vec![];
+    let mut seen = HashMap::new();
+
+    for entry in &properties.entries {
+        if let Some(first) = seen.insert(entry.key.as_str(), entry.line) {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                line: Some(entry.line),
+                message: format!(
+                    "{} is already set on line {first}, this value wins",
+                    entry.key
+                ),
+            });
+        }
+
+        let Some(key) = known.get(entry.key.as_str()) else {
+            let message = match suggest(&entry.key) {
+                Some(suggestion) => {
+                    format!("unknown key {}, did you mean {suggestion}?", entry.key)
+                }
+                None => format!("unknown key {}", entry.key),
+            };
+
+            issues.push(Issue {
+                severity: Severity::Warning,
+                line: Some(entry.line),
+                message,
+            });
+            continue;
+        };
+
+        if let Err(message) = key.kind.check(entry.value.trim()) {
+            issues.push(Issue {
+                severity: Severity::Error,
+                line: Some(entry.line),
+                message: format!("{}: {message}", entry.key),
+            });
+        }
+    }
+
+    let mut missing = KEYS
+        .iter()
+        .filter(|key| key.required)
+        .map(|key| key.name)
+        .filter(|name| properties.get(name).is_none())
+        .collect::<Vec<_>>();
+
+    if properties.get("streamName").is_none() && properties.get("streamArn").is_none() {
+        missing.push("streamName (or streamArn)");
+    }
+
+    for name in missing {
+        issues.push(Issue {
+            severity: Severity::Error,
+            line: None,
+            message: format!("missing required key {name}"),
+        });
+    }
+
+    issues
+}
+
+/// The closest known key, if any is close enough to be a typo.
+fn suggest(name: &str) -> Option<&'static str> {
+    let lower = name.to_ascii_lowercase();
+
+    KEYS.iter()
+        .map(|key| (distance(&lower, &key.name.to_ascii_lowercase()), key.name))
+        .filter(|(distance, _)| *distance <= 3.max(name.len() / 5))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Values for a starter properties file.
+#[derive(Debug, Clone, Default)]
+pub struct Starter {
+    pub executable_name: String,
+    pub application_name: String,
+    pub stream_name: String,
+    pub region_name: Option<String>,
+    pub initial_position: Option<String>,
+}
+
+/// Generates a minimal properties file which passes [`validate`].
+pub fn generate(starter: &Starter) -> String {
+    let mut lines = vec![
+        "# Generated by kcl-bootstrap.".to_string(),
+        "# All available KCL options: <https://github.com/awslabs/amazon-kinesis-client/blob/master/docs/kcl-configurations.md>".to_string(),
+        String::new(),
+        "# The program implementing the MultiLang protocol.".to_string(),
+        format!("executableName = {}", escape(&starter.executable_name)),
+        String::new(),
+        "# Also names the DynamoDB lease table.".to_string(),
+        format!("applicationName = {}", escape(&starter.application_name)),
+        String::new(),
+        format!("streamName = {}", escape(&starter.stream_name)),
+    ];
+
+    if let Some(region) = &starter.region_name {
+        lines.push(format!("regionName = {}", escape(region)));
+    }
+
+    lines.push(format!(
+        "initialPositionInStream = {}",
+        escape(
+            starter
+                .initial_position
+                .as_deref()
+                .unwrap_or("TRIM_HORIZON")
+        )
+    ));
+    lines.push("processingLanguage = rust".to_string());
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(properties: &Properties) -> Vec<(&str, &str)> {
+        properties
+            .entries
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_separators() {
+        let properties = Properties::parse(
+            "a=1\nb:2\nc 3\nd = 4\ne\t:\t5\nf  =  x = y\ng\nh=\n  i = indented\nj=trailing  \n",
+        );
+
+        assert_eq!(
+            pairs(&properties),
+            [
+                ("a", "1"),
+                ("b", "2"),
+                ("c", "3"),
+                ("d", "4"),
+                ("e", "5"),
+                ("f", "x = y"),
+                ("g", ""),
+                ("h", ""),
+                ("i", "indented"),
+                ("j", "trailing  "),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let properties = Properties::parse("# comment\n! comment \\\n\n   \na = 1\n  # indented\n");
+
+        assert_eq!(pairs(&properties), [("a", "1")]);
+        assert_eq!(properties.entries[0].line, 5);
+    }
+
+    #[test]
+    fn joins_continuation_lines() {
+        let properties =
+            Properties::parse("list = a, \\\n       b, \\\n       c\nnext = 1\nend = \\\n");
+
+        assert_eq!(
+            pairs(&properties),
+            [("list", "a, b, c"), ("next", "1"), ("end", "")]
+        );
+        assert_eq!(properties.entries[1].line, 4);
+    }
+
+    #[test]
+    fn even_backslashes_do_not_continue() {
+        let properties = Properties::parse("path = C:\\\\\nnext = 1\n");
+
+        assert_eq!(pairs(&properties), [("path", "C:\\"), ("next", "1")]);
+    }
+
+    #[test]
+    fn unescapes() {
+        let properties = Properties::parse(
+            "a\\=b\\:c\\ d = 1\ntabs = x\\ty\\nz\\r\\f\nunicode = \\u00e9t\\u00E9\npair = \\uD83D\\uDE00\ninvalid = \\u12x\nlone = \\uD83Dx\nother = \\q\n",
+        );
+
+        assert_eq!(
+            pairs(&properties),
+            [
+                ("a=b:c d", "1"),
+                ("tabs", "x\ty\nz\r\u{c}"),
+                ("unicode", "été"),
+                ("pair", "😀"),
+                ("invalid", "\\u12x"),
+                ("lone", "\\uD83Dx"),
+                ("other", "q"),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_values_win() {
+        let mut properties = Properties::parse("a = 1\nb = 2\na = 3\n");
+        assert_eq!(properties.get("a"), Some("3"));
+
+        properties.set("a", "4");
+        assert_eq!(pairs(&properties), [("b", "2"), ("a", "4")]);
+        assert_eq!(properties.entries[1].line, 1);
+    }
+
+    #[test]
+    fn escapes_for_java() {
+        let properties = Properties {
+            entries: vec![Entry {
+                key: "key with = and :".to_string(),
+                value: " été 😀\\".to_string(),
+                line: 0,
+            }],
+        };
+
+        assert_eq!(
+            properties.to_string(),
+            "key\\ with\\ \\=\\ and\\ \\: = \\ \\u00E9t\\u00E9 \\uD83D\\uDE00\\\\\n"
+        );
+        assert!(properties.to_string().is_ascii());
+    }
+
+    #[test]
+    fn round_trips() {
+        let entries = [
+            ("plain", "value"),
+            ("key with spaces", "  leading spaces"),
+            ("#not a comment", "!neither"),
+            ("sep=ar:ators", "a = b : c"),
+            ("multi", "line\nbreaks\r\nand\ttabs"),
+            ("backslash\\", "C:\\path\\"),
+            ("ünïcödé", "😀 and é"),
+            ("empty", ""),
+        ];
+
+        let properties = Properties {
+            entries: entries
+                .iter()
+                .map(|(key, value)| Entry {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    line: 0,
+                })
+                .collect(),
+        };
+
+        assert_eq!(pairs(&Properties::parse(&properties.to_string())), entries);
+    }
+
+    #[test]
+    fn generated_file_is_valid() {
+        let generated = generate(&Starter {
+            executable_name: "./target/release/app".to_string(),
+            application_name: "app".to_string(),
+            stream_name: "stream".to_string(),
+            region_name: Some("eu-west-1".to_string()),
+            initial_position: None,
+        });
+
+        let properties = Properties::parse(&generated);
+        assert_eq!(
+            properties.get("executableName"),
+            Some("./target/release/app")
+        );
+        assert_eq!(
+            properties.get("initialPositionInStream"),
+            Some("TRIM_HORIZON")
+        );
+        assert!(validate(&properties).is_empty());
+    }
+}
@@ -0,0 +1,655 @@
+use reqwest::blocking::Client;
+use reqwest::header::RANGE;
+use reqwest::{StatusCode, Url};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, copy};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::verify::{Algorithm, Checksum, Verification, verify_signature};
+use crate::xml::Element;
+
+pub const MAVEN_CENTRAL: &str = "https://repo1.maven.org/maven2";
+
+/// Attempts per file and repository before giving up on it.
+const DOWNLOAD_ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub id: String,
+    pub url: String,
+    pub credentials: Option<Credentials>,
+}
+
+impl Repository {
+    pub fn new(id: &str, url: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+            credentials: None,
+        }
+    }
+
+    pub fn central() -> Self {
+        Self::new("central", MAVEN_CENTRAL)
+    }
+
+    /// Reads `KCL_BOOTSTRAP_<ID>_USERNAME` and `KCL_BOOTSTRAP_<ID>_PASSWORD`,
+    /// with the id in upper case and other characters than letters and digits
+    /// replaced by `_`.
+    pub fn credentials_from_env(&self) -> Option<Credentials> {
+        let id = self.sanitized_id().to_ascii_uppercase();
+
+        Some(Credentials {
+            username: std::env::var(format!("KCL_BOOTSTRAP_{id}_USERNAME")).ok()?,
+            password: std::env::var(format!("KCL_BOOTSTRAP_{id}_PASSWORD")).ok()?,
+        })
+    }
+
+    /// The id with other characters than letters and digits replaced by `_`.
+    fn sanitized_id(&self) -> String {
+        self.id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    fn is_external(&self) -> bool {
+        let host = self
+            .url
+            .split_once("://")
+            .map(|(_, rest)| rest.split(['/', ':']).next().unwrap_or_default())
+            .unwrap_or_default();
+
+        !self.url.starts_with("file:") && !matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+    }
+}
+
+impl FromStr for Repository {
+    type Err = String;
+
+    /// Parses `ID=URL`, or just `URL` which is then also used as id.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, url) = match s.split_once('=') {
+            Some((id, url)) if !id.contains("://") => (id, url),
+            _ => (s, s),
+        };
+
+        if id.is_empty() || !url.contains("://") {
+            return Err(format!("expected ID=URL, found {s:?}"));
+        }
+
+        match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https" | "file") => {
+                Ok(Self::new(id, url))
+            }
+            Ok(parsed) => Err(format!(
+                "unsupported scheme {:?} in {url}, expected http, https or file",
+                parsed.scheme()
+            )),
+            Err(err) => Err(format!("invalid URL {url}: {err}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Mirror {
+    pub id: String,
+    pub url: String,
+    pub mirror_of: String,
+}
+
+impl Mirror {
+    /// Supports `*`, `external:*`, ids and `!id` exclusions separated by commas.
+    pub fn matches(&self, repository: &Repository) -> bool {
+        let mut matched = false;
+
+        for pattern in self.mirror_of.split(',').map(str::trim) {
+            if let Some(excluded) = pattern.strip_prefix('!') {
+                if excluded == repository.id {
+                    return false;
+                }
+            } else if pattern == "*"
+                || pattern == repository.id
+                || (pattern == "external:*" && repository.is_external())
+            {
+                matched = true;
+            }
+        }
+
+        matched
+    }
+}
+
+/// The parts of a Maven `settings.xml` relevant for fetching artifacts.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub local_repository: Option<PathBuf>,
+    pub offline: bool,
+    pub mirrors: Vec<Mirror>,
+    pub servers: HashMap<String, Credentials>,
+}
+
+impl Settings {
+    /// `~/.m2/settings.xml`
+    pub fn default_path() -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".m2").join("settings.xml"))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        eprintln!("Parsing Maven settings: {}", path.display());
+
+        let settings = Element::parse(BufReader::new(File::open(path)?))?;
+        let home = home_dir().unwrap_or_default();
+
+        let mirrors = settings
+            .child("mirrors")
+            .into_iter()
+            .flat_map(|mirrors| mirrors.children_named("mirror"))
+            .filter_map(|mirror| {
+                Some(Mirror {
+                    id: mirror.child_text("id")?.to_string(),
+                    url: mirror.child_text("url")?.trim_end_matches('/').to_string(),
+                    mirror_of: mirror.child_text("mirrorOf")?.to_string(),
+                })
+            })
+            .collect();
+
+        let servers = settings
+            .child("servers")
+            .into_iter()
+            .flat_map(|servers| servers.children_named("server"))
+            .filter_map(|server| {
+                Some((
+                    server.child_text("id")?.to_string(),
+                    Credentials {
+                        username: server.child_text("username")?.to_string(),
+                        password: server
+                            .child_text("password")
+                            .unwrap_or_default()
+                            .to_string(),
+                    },
+                ))
+            })
+            .collect();
+
+        Ok(Self {
+            local_repository: settings
+                .child_text("localRepository")
+                .filter(|path| !path.is_empty())
+                .map(|path| PathBuf::from(path.replace("${user.home}", &home.to_string_lossy()))),
+            offline: settings.child_text("offline") == Some("true"),
+            mirrors,
+            servers,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    pub path: PathBuf,
+
+    /// URL of the remote repository the file was downloaded from, unknown for
+    /// files from the local repository.
+    pub repository: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Statistics {
+    pub downloaded: usize,
+    pub bytes: u64,
+}
+
+/// Where artifacts are fetched from: the local Maven repository first, then
+/// the remote repositories in order, which is their priority.
+#[derive(Debug)]
+pub struct Repositories {
+    pub local: Option<PathBuf>,
+    pub remotes: Vec<Repository>,
+
+    /// Only use the local repository and files fetched earlier.
+    pub offline: bool,
+
+    client: Client,
+    downloaded: AtomicUsize,
+    bytes: AtomicU64,
+}
+
+impl Repositories {
+    /// Applies the mirrors and credentials of `settings` to `remotes`.
+    ///
+    /// Credentials from the environment take precedence over those from the
+    /// settings. The local repository defaults to `~/.m2/repository` if it exists.
+    pub fn new(remotes: Vec<Repository>, settings: &Settings) -> Self {
+        let mut mirrored: Vec<Repository> = vec![];
+
+        for repository in remotes {
+            let mut repository = match settings.mirrors.iter().find(|m| m.matches(&repository)) {
+                Some(mirror) => {
+                    eprintln!("Using mirror {} for {}", mirror.id, repository.id);
+                    Repository::new(&mirror.id, &mirror.url)
+                }
+                None => repository,
+            };
+
+            if mirrored.iter().any(|existing| existing.id == repository.id) {
+                continue;
+            }
+
+            repository.credentials = repository
+                .credentials_from_env()
+                .or_else(|| settings.servers.get(&repository.id).cloned())
+                .or(repository.credentials);
+
+            mirrored.push(repository);
+        }
+
+        let local = settings.local_repository.clone().or_else(|| {
+            home_dir()
+                .map(|home| home.join(".m2").join("repository"))
+                .filter(|path| path.is_dir())
+        });
+
+        Self {
+            local,
+            remotes: mirrored,
+            offline: settings.offline,
+            client: Client::new(),
+            downloaded: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Files downloaded so far, including POMs, checksums and signatures.
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            downloaded: self.downloaded.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Fetches the file at `path` (relative to the repository root) and
+    /// verifies it.
+    ///
+    /// Files from the local repository are used in place, others are
+    /// downloaded into `folder` unless they are already there. Cached files
+    /// are verified again each time. Where a file was downloaded from is
+    /// remembered in `<file>.repository`.
+    pub fn fetch(
+        &self,
+        path: &str,
+        folder: &Path,
+        verification: &Verification,
+    ) -> Result<Fetched, Box<dyn std::error::Error>> {
+        self.fetch_from(path, folder, verification, None)
+    }
+
+    /// Like [`Repositories::fetch`], but only downloads from the remote
+    /// repository with the `origin` URL if given, even if it is not configured.
+    pub fn fetch_from(
+        &self,
+        path: &str,
+        folder: &Path,
+        verification: &Verification,
+        origin: Option<&str>,
+    ) -> Result<Fetched, Box<dyn std::error::Error>> {
+        if let Some(local_path) = self
+            .local
+            .as_ref()
+            .map(|local| local.join(path))
+            .filter(|local_path| local_path.exists())
+        {
+            eprintln!("Found in local repository: {}", local_path.display());
+            self.verify(path, &local_path, folder, verification, None)?;
+            return Ok(Fetched {
+                path: local_path,
+                repository: None,
+            });
+        }
+
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        let dest_path = folder.join(file_name);
+        let origin_path = with_suffix(&dest_path, "repository");
+        let cached = dest_path.exists();
+
+        let repository = if cached {
+            eprintln!("Already exists: {}", dest_path.display());
+            fs::read_to_string(&origin_path)
+                .ok()
+                .map(|url| url.trim().to_string())
+        } else {
+            match self.download(path, &dest_path, origin)? {
+                Some(url) => Some(url),
+                None => return Err(self.not_found(path).into()),
+            }
+        };
+
+        let result = self.verify(
+            path,
+            &dest_path,
+            folder,
+            verification,
+            repository.as_deref(),
+        );
+
+        if result.is_err() && !cached {
+            // Never leave an unverified file behind to be picked up as cached
+            let _ = fs::remove_file(&dest_path);
+        }
+
+        result?;
+
+        if !cached && let Some(url) = &repository {
+            fs::write(&origin_path, url)?;
+        }
+
+        Ok(Fetched {
+            path: dest_path,
+            repository,
+        })
+    }
+
+    /// Checks `file` against its checksum and signature, which are fetched
+    /// from the `origin` repository URL if known.
+    fn verify(
+        &self,
+        path: &str,
+        file: &Path,
+        folder: &Path,
+        verification: &Verification,
+        origin: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let checksum = self.fetch_checksum(path, file, folder, origin)?;
+        checksum.verify(file)?;
+        eprintln!(
+            "Verified {} checksum: {}",
+            checksum.algorithm.extension(),
+            file.display()
+        );
+
+        if let Some(keyring) = &verification.keyring {
+            let signature_path = self
+                .sidecar(path, file, folder, "asc", origin)?
+                .ok_or_else(|| self.not_found(&format!("{path}.asc")))?;
+
+            verify_signature(file, &signature_path, keyring)?;
+            eprintln!("Verified signature: {}", file.display());
+        }
+
+        Ok(())
+    }
+
+    /// Loads the strongest checksum available.
+    fn fetch_checksum(
+        &self,
+        path: &str,
+        file: &Path,
+        folder: &Path,
+        origin: Option<&str>,
+    ) -> Result<Checksum, Box<dyn std::error::Error>> {
+        for algorithm in Algorithm::ALL {
+            if let Some(checksum_path) =
+                self.sidecar(path, file, folder, algorithm.extension(), origin)?
+            {
+                return Checksum::parse(algorithm, &fs::read_to_string(&checksum_path)?);
+            }
+        }
+
+        Err(format!("No checksum available for {path}").into())
+    }
+
+    /// Finds `<file>.<suffix>` next to the file or in `folder`, downloading it
+    /// if necessary.
+    fn sidecar(
+        &self,
+        path: &str,
+        file: &Path,
+        folder: &Path,
+        suffix: &str,
+        origin: Option<&str>,
+    ) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        let beside = with_suffix(file, suffix);
+
+        if beside.exists() {
+            return Ok(Some(beside));
+        }
+
+        let file_name = file.file_name().unwrap_or_default();
+        let in_folder = with_suffix(&folder.join(file_name), suffix);
+
+        if in_folder.exists()
+            || self
+                .download(&format!("{path}.{suffix}"), &in_folder, origin)?
+                .is_some()
+        {
+            return Ok(Some(in_folder));
+        }
+
+        Ok(None)
+    }
+
+    /// Tries all remote repositories in order, or only the one with the
+    /// `origin` URL if given. Returns the URL of the repository the file was
+    /// downloaded from, `None` if none has it or when offline.
+    ///
+    /// The file is written to `<dest>.<repository id>.part` first and only
+    /// renamed once complete, a `.part` file left behind by an interrupted
+    /// download from the same repository is resumed.
+    fn download(
+        &self,
+        path: &str,
+        dest_path: &Path,
+        origin: Option<&str>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if self.offline {
+            return Ok(None);
+        }
+
+        if let Some(folder) = dest_path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        let unconfigured;
+        let remotes = match origin {
+            Some(url) => match self.remotes.iter().find(|repository| repository.url == url) {
+                Some(repository) => vec![repository],
+                None => {
+                    let mut repository = Repository::new(url, url);
+                    repository.credentials = repository.credentials_from_env();
+                    unconfigured = repository;
+                    vec![&unconfigured]
+                }
+            },
+            None => self.remotes.iter().collect(),
+        };
+
+        for repository in remotes {
+            let url = format!("{}/{path}", repository.url);
+
+            match self.download_with_retry(repository, &url, &part_path(dest_path, repository)) {
+                Ok(true) => {
+                    fs::rename(part_path(dest_path, repository), dest_path)?;
+                    self.downloaded.fetch_add(1, Ordering::Relaxed);
+                    eprintln!("Saved to: {}", dest_path.display());
+
+                    // Partial downloads from other repositories are of no use anymore
+                    for other in &self.remotes {
+                        let _ = fs::remove_file(part_path(dest_path, other));
+                    }
+
+                    return Ok(Some(repository.url.clone()));
+                }
+                Ok(false) => {}
+                Err(err) => eprintln!("Failed to fetch {url}: {err}"),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Retries transient failures with exponential backoff.
+    fn download_with_retry(
+        &self,
+        repository: &Repository,
+        url: &str,
+        part_path: &Path,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            match self.try_download(repository, url, part_path) {
+                Ok(found) => return Ok(found),
+                Err(DownloadError::Transient(err)) if attempt < DOWNLOAD_ATTEMPTS => {
+                    eprintln!(
+                        "Attempt {attempt}/{DOWNLOAD_ATTEMPTS} for {url} failed: {err}, retrying in {delay:?}"
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(DownloadError::Transient(err) | DownloadError::Permanent(err)) => {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    fn try_download(
+        &self,
+        repository: &Repository,
+        url: &str,
+        part_path: &Path,
+    ) -> Result<bool, DownloadError> {
+        if let Some(source) = local_path(url) {
+            return self.copy_local(&source, part_path);
+        }
+
+        let offset = fs::metadata(part_path).map(|meta| meta.len()).unwrap_or(0);
+
+        eprintln!("Downloading from URL: {url}");
+        let mut request = self.client.get(url).header("User-Agent", "Mozilla/5.0");
+
+        if let Some(credentials) = &repository.credentials {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={offset}-"));
+        }
+
+        let mut resp = request
+            .send()
+            .map_err(|err| DownloadError::Transient(err.into()))?;
+        let status = resp.status();
+
+        if status == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file does not match the remote one, start over
+            let _ = fs::remove_file(part_path);
+            return Err(DownloadError::Transient(
+                "partial download does not match".into(),
+            ));
+        }
+
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(DownloadError::Transient(status.to_string().into()));
+        }
+
+        if !status.is_success() {
+            return Err(DownloadError::Permanent(status.to_string().into()));
+        }
+
+        let mut out = if status == StatusCode::PARTIAL_CONTENT {
+            eprintln!("Resuming {url} at byte {offset}");
+            OpenOptions::new().append(true).open(part_path)
+        } else {
+            File::create(part_path)
+        }
+        .map_err(|err| DownloadError::Permanent(err.into()))?;
+
+        // Whatever was written so far is kept for the next attempt to resume
+        let bytes =
+            copy(&mut resp, &mut out).map_err(|err| DownloadError::Transient(err.into()))?;
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        Ok(true)
+    }
+
+    /// Copies from a `file:` repository, which the HTTP client cannot read.
+    fn copy_local(&self, source: &Path, part_path: &Path) -> Result<bool, DownloadError> {
+        if !source.is_file() {
+            return Ok(false);
+        }
+
+        eprintln!("Copying from: {}", source.display());
+        let bytes =
+            fs::copy(source, part_path).map_err(|err| DownloadError::Permanent(err.into()))?;
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        Ok(true)
+    }
+
+    fn not_found(&self, path: &str) -> String {
+        if self.offline {
+            return format!("{path} is not available offline");
+        }
+
+        let ids = self
+            .remotes
+            .iter()
+            .map(|repository| repository.id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("{path} not found in any repository ({ids})")
+    }
+}
+
+enum DownloadError {
+    /// Worth retrying, e.g. connection problems or server errors.
+    Transient(Box<dyn std::error::Error>),
+    Permanent(Box<dyn std::error::Error>),
+}
+
+/// Path of a `file:` URL.
+fn local_path(url: &str) -> Option<PathBuf> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()
+}
+
+/// Partial download of `dest_path` from `repository`; resuming it from another
+/// repository would mix up different files.
+fn part_path(dest_path: &Path, repository: &Repository) -> PathBuf {
+    with_suffix(dest_path, &format!("{}.part", repository.sanitized_id()))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    path.into()
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::maven::MavenPackage;
+use crate::pom::{Dependency, Exclusion, Pom};
+
+/// Resolves the runtime classpath of `roots` including transitive dependencies.
+///
+/// The graph is walked breadth-first, so like Maven the version closest to the
+/// roots wins and declaration order breaks ties. Test, provided and optional
+/// dependencies are not followed and exclusions apply to the whole subtree
+/// below the dependency declaring them.
+///
+/// `managed` is the dependency management of the root POM, which also pins
+/// the versions of transitive dependencies. `fetch_pom` returns POMs as
+/// parsed, parents and imported BOMs are fetched through it as well.
+pub fn resolve(
+    roots: &[Dependency],
+    managed: &[Dependency],
+    mut fetch_pom: impl FnMut(&MavenPackage) -> Result<Pom, Box<dyn std::error::Error>>,
+) -> Result<Vec<MavenPackage>, Box<dyn std::error::Error>> {
+    // Parent POMs are shared by many artifacts
+    let mut cache = HashMap::<String, Pom>::new();
+    let mut fetch_pom = |package: &MavenPackage| {
+        let coordinates = package.to_string();
+
+        if let Some(pom) = cache.get(&coordinates) {
+            return Ok(pom.clone());
+        }
+
+        let pom = fetch_pom(package)?;
+        cache.insert(coordinates, pom.clone());
+        Ok(pom)
+    };
+
+    let managed = managed
+        .iter()
+        .map(|dependency| (dependency.key(), dependency))
+        .collect::<HashMap<_, _>>();
+
+    let mut queue = roots
+        .iter()
+        .filter(|dependency| dependency.is_runtime())
+        .map(|dependency| (dependency.clone(), vec![]))
+        .collect::<VecDeque<(Dependency, Vec<Exclusion>)>>();
+
+    let mut seen = HashSet::new();
+    let mut packages = vec![];
+
+    while let Some((mut dependency, mut exclusions)) = queue.pop_front() {
+        if !seen.insert(dependency.key()) {
+            continue;
+        }
+
+        if let Some(managed) = managed.get(&dependency.key())
+            && managed.version.is_some()
+        {
+            dependency.version.clone_from(&managed.version);
+        }
+
+        let Some(package) = dependency.package() else {
+            eprintln!(
+                "Skipping dependency without version: {}:{}",
+                dependency.group_id, dependency.artifact_id
+            );
+            continue;
+        };
+
+        eprintln!("Resolved dependency: {package}");
+
+        let pom = fetch_pom(&package)?.effective(&mut fetch_pom)?;
+
+        if package.is_jar() {
+            packages.push(package);
+        }
+
+        exclusions.extend(dependency.exclusions);
+
+        for child in pom.dependencies {
+            if !child.is_runtime() || child.optional {
+                continue;
+            }
+
+            if exclusions
+                .iter()
+                .any(|exclusion| exclusion.matches(&child.group_id, &child.artifact_id))
+            {
+                continue;
+            }
+
+            queue.push_back((child, exclusions.clone()));
+        }
+    }
+
+    eprintln!("Total dependencies resolved: {}", packages.len());
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// POM of `g:<artifact_id>:<version>` with the given `<dependency>` contents.
+    fn pom(artifact_id: &str, version: &str, dependencies: &[&str]) -> (String, String) {
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| format!("<dependency><groupId>g</groupId>{dependency}</dependency>"))
+            .collect::<String>();
+
+        (
+            format!("g:{artifact_id}:{version}"),
+            format!(
+                "<project><groupId>g</groupId><artifactId>{artifact_id}</artifactId><version>{version}</version><dependencies>{dependencies}</dependencies></project>"
+            ),
+        )
+    }
+
+    fn dependency(artifact_id: &str, version: &str) -> Dependency {
+        Dependency::from_package(&MavenPackage::new("g", artifact_id, version))
+    }
+
+    fn resolved(
+        roots: &[Dependency],
+        managed: &[Dependency],
+        poms: &[(String, String)],
+    ) -> Vec<String> {
+        resolve(roots, managed, |package| {
+            let coordinates = format!(
+                "{}:{}:{}",
+                package.group_id, package.artifact_id, package.version
+            );
+
+            match poms.iter().find(|(key, _)| *key == coordinates) {
+                Some((_, xml)) => Pom::parse(xml),
+                None => Err(format!("unknown POM {coordinates}").into()),
+            }
+        })
+        .unwrap()
+        .iter()
+        .map(MavenPackage::to_string)
+        .collect()
+    }
+
+    #[test]
+    fn nearest_version_wins() {
+        let poms = [
+            pom(
+                "a",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom(
+                "b",
+                "1",
+                &["<artifactId>d</artifactId><version>1</version>"],
+            ),
+            pom(
+                "d",
+                "1",
+                &["<artifactId>c</artifactId><version>2</version>"],
+            ),
+            pom("c", "1", &[]),
+        ];
+
+        assert_eq!(
+            resolved(&[dependency("b", "1"), dependency("a", "1")], &[], &poms),
+            ["g:b:1", "g:a:1", "g:d:1", "g:c:1"]
+        );
+    }
+
+    #[test]
+    fn declaration_order_breaks_ties() {
+        let poms = [
+            pom(
+                "a",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom(
+                "b",
+                "1",
+                &["<artifactId>c</artifactId><version>2</version>"],
+            ),
+            pom("c", "1", &[]),
+            pom("c", "2", &[]),
+        ];
+
+        assert_eq!(
+            resolved(&[dependency("b", "1"), dependency("a", "1")], &[], &poms),
+            ["g:b:1", "g:a:1", "g:c:2"]
+        );
+    }
+
+    #[test]
+    fn skips_non_runtime_and_optional_dependencies() {
+        let poms = [
+            pom(
+                "a",
+                "1",
+                &[
+                    "<artifactId>test</artifactId><version>1</version><scope>test</scope>",
+                    "<artifactId>provided</artifactId><version>1</version><scope>provided</scope>",
+                    "<artifactId>optional</artifactId><version>1</version><optional>true</optional>",
+                    "<artifactId>runtime</artifactId><version>1</version><scope>runtime</scope>",
+                ],
+            ),
+            pom("runtime", "1", &[]),
+        ];
+
+        let mut test_root = dependency("test", "1");
+        test_root.scope = Some("test".to_string());
+
+        assert_eq!(
+            resolved(&[dependency("a", "1"), test_root], &[], &poms),
+            ["g:a:1", "g:runtime:1"]
+        );
+    }
+
+    #[test]
+    fn exclusions_apply_to_the_subtree() {
+        let poms = [
+            pom(
+                "a",
+                "1",
+                &["<artifactId>b</artifactId><version>1</version>"],
+            ),
+            pom(
+                "b",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom(
+                "x",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom("c", "1", &[]),
+        ];
+
+        let mut a = dependency("a", "1");
+        a.exclusions.push(Exclusion {
+            group_id: "g".to_string(),
+            artifact_id: "c".to_string(),
+        });
+
+        assert_eq!(resolved(&[a.clone()], &[], &poms), ["g:a:1", "g:b:1"]);
+
+        // Other paths to the excluded artifact still bring it in
+        assert_eq!(
+            resolved(&[a, dependency("x", "1")], &[], &poms),
+            ["g:a:1", "g:x:1", "g:b:1", "g:c:1"]
+        );
+    }
+
+    #[test]
+    fn managed_versions_pin_transitive_dependencies() {
+        let poms = [
+            pom(
+                "a",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom("c", "3", &[]),
+        ];
+
+        assert_eq!(
+            resolved(&[dependency("a", "1")], &[dependency("c", "3")], &poms),
+            ["g:a:1", "g:c:3"]
+        );
+    }
+
+    #[test]
+    fn pom_dependencies_only_contribute_their_dependencies() {
+        let poms = [
+            pom(
+                "bundle",
+                "1",
+                &["<artifactId>c</artifactId><version>1</version>"],
+            ),
+            pom("c", "1", &[]),
+        ];
+
+        let mut bundle = dependency("bundle", "1");
+        bundle.kind = Some("pom".to_string());
+
+        assert_eq!(resolved(&[bundle], &[], &poms), ["g:c:1"]);
+    }
+}
@@ -0,0 +1,123 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::copy;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Default)]
+pub struct Verification {
+    /// Keyring to check `.asc` signatures against; signatures are not
+    /// checked without one.
+    pub keyring: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Sha1,
+}
+
+impl Algorithm {
+    /// Preferred first.
+    pub const ALL: [Algorithm; 2] = [Algorithm::Sha256, Algorithm::Sha1];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha1 => "sha1",
+        }
+    }
+
+    pub fn digest_file(&self, path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let mut file = File::open(path)?;
+
+        let digest = match self {
+            Algorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                copy(&mut file, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+            Algorithm::Sha1 => {
+                let mut hasher = Sha1::new();
+                copy(&mut file, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+        };
+
+        Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub hex: String,
+}
+
+impl Checksum {
+    /// Parses the content of a checksum file, which may be followed by a file name.
+    pub fn parse(algorithm: Algorithm, content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let hex = content
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let expected_len = match algorithm {
+            Algorithm::Sha256 => 64,
+            Algorithm::Sha1 => 40,
+        };
+
+        if hex.len() != expected_len || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("Invalid {} checksum: {content:?}", algorithm.extension()).into());
+        }
+
+        Ok(Self { algorithm, hex })
+    }
+
+    pub fn verify(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let actual = self.algorithm.digest_file(path)?;
+
+        if actual != self.hex {
+            return Err(format!(
+                "Checksum mismatch for {}: expected {} {}, found {actual}",
+                path.display(),
+                self.algorithm.extension(),
+                self.hex
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks a detached `.asc` signature with `gpgv`.
+pub fn verify_signature(
+    path: &Path,
+    signature: &Path,
+    keyring: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // gpgv looks up relative keyrings in its home directory
+    let keyring = std::path::absolute(keyring)?;
+
+    let output = Command::new("gpgv")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(signature)
+        .arg(path)
+        .output()
+        .map_err(|err| format!("Failed to run gpgv: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Signature verification failed for {}:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(())
+}
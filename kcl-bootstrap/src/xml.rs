@@ -1,108 +1,117 @@
-use crate::maven::MavenPackage;
 use quick_xml::Reader;
 use quick_xml::events::Event;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-
-pub fn parse_pom(path: &str) -> Result<Vec<MavenPackage>, Box<dyn std::error::Error>> {
-    eprintln!("Parsing POM: {path}");
-    let file = File::open(path)?;
-    let mut reader = Reader::from_reader(BufReader::new(file));
-    reader.config_mut().trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut current_tag = String::new();
-
-    let mut in_dependency = false;
-    let mut in_properties = false;
-    let mut in_exclusions = false;
-
-    let mut group_id = String::new();
-    let mut artifact_id = String::new();
-    let mut version = String::new();
-
-    let mut versions_map = HashMap::new();
-    let mut packages = vec![];
-
-    while let Ok(ev) = reader.read_event_into(&mut buf) {
-        match ev {
-            Event::Eof => break,
-
-            Event::Start(ref e) => {
-                let tag = strip_ns(e.name().into_inner());
-                current_tag = tag.to_string();
-
-                match current_tag.as_str() {
-                    "dependency" => {
-                        in_dependency = true;
-                        group_id.clear();
-                        artifact_id.clear();
-                        version.clear();
+use std::io::BufRead;
+
+/// XML element with its trimmed text content and child elements.
+///
+/// Attributes are not kept since POM files do not use them.
+#[derive(Debug, Clone, Default)]
+pub struct Element {
+    pub name: String,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    /// Parses a document and returns its root element.
+    pub fn parse(reader: impl BufRead) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = Reader::from_reader(reader);
+        let mut buf = Vec::new();
+
+        // Placeholder for the document itself
+        let mut stack = vec![Element::default()];
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Eof => break,
+
+                Event::Start(ref e) => stack.push(Element {
+                    name: strip_ns(e.name().into_inner()).to_string(),
+                    ..Default::default()
+                }),
+
+                Event::Empty(ref e) => {
+                    let element = Element {
+                        name: strip_ns(e.name().into_inner()).to_string(),
+                        ..Default::default()
+                    };
+
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
                     }
-                    "properties" => in_properties = true,
-                    "exclusions" => in_exclusions = true,
-                    _ => {}
                 }
-            }
 
-            Event::End(ref e) => {
-                let tag = strip_ns(e.name().into_inner());
-
-                match tag {
-                    "dependency" => {
-                        in_dependency = false;
-                        if in_exclusions {
-                            // skip dependencies inside <exclusions>
-                        } else if !group_id.is_empty()
-                            && !artifact_id.is_empty()
-                            && !version.is_empty()
-                        {
-                            let version_clean = versions_map
-                                .get(&version)
-                                .cloned()
-                                .unwrap_or(version.clone());
-
-                            packages.push(MavenPackage::new(
-                                &group_id,
-                                &artifact_id,
-                                &version_clean,
-                            ));
-
-                            eprintln!(
-                                "Parsed dependency: {group_id}:{artifact_id}:{version_clean}"
-                            );
-                        }
+                Event::End(_) => {
+                    let mut element = stack.pop().ok_or("unbalanced XML document")?;
+                    element.text = element.text.trim().to_string();
+
+                    stack
+                        .last_mut()
+                        .ok_or("unbalanced XML document")?
+                        .children
+                        .push(element);
+                }
+
+                Event::Text(ref e) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&e.decode()?);
                     }
-                    "properties" => in_properties = false,
-                    "exclusions" => in_exclusions = false,
-                    _ => {}
                 }
-            }
 
-            Event::Text(e) => {
-                let text = e.decode()?.into_owned();
-
-                if in_properties {
-                    versions_map.insert(format!("${{{current_tag}}}"), text);
-                } else if in_dependency && !in_exclusions {
-                    match current_tag.as_str() {
-                        "groupId" => group_id = text,
-                        "artifactId" => artifact_id = text,
-                        "version" => version = text,
-                        _ => {}
+                Event::CData(ref e) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&e.decode()?);
                     }
                 }
+
+                Event::GeneralRef(ref e) => {
+                    let resolved = match e.resolve_char_ref()? {
+                        Some(c) => c,
+                        None => match e.decode()?.as_ref() {
+                            "amp" => '&',
+                            "lt" => '<',
+                            "gt" => '>',
+                            "quot" => '"',
+                            "apos" => '\'',
+                            other => return Err(format!("unknown XML entity: &{other};").into()),
+                        },
+                    };
+
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push(resolved);
+                    }
+                }
+
+                _ => {}
             }
 
-            _ => {}
+            buf.clear();
+        }
+
+        let document = stack.pop().ok_or("unbalanced XML document")?;
+
+        if !stack.is_empty() {
+            return Err("unbalanced XML document".into());
         }
 
-        buf.clear();
+        document
+            .children
+            .into_iter()
+            .next()
+            .ok_or_else(|| "empty XML document".into())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
     }
 
-    eprintln!("Total dependencies parsed: {}", packages.len());
-    Ok(packages)
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
 }
 
 fn strip_ns(tag: &[u8]) -> &str {
@@ -0,0 +1,197 @@
+//! Plays the MultiLangDaemon for a worker executable, feeding it records from
+//! files or stdin instead of Kinesis.
+
+use std::io::BufRead as _;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use kcl_async::daemon::Daemon;
+use kcl_async::dedup::FileSequenceStore;
+use kcl_async::local::{
+    Format, LocalControl, LocalError, LocalOptions, LocalTransport, Simulate, Source,
+};
+use kcl_async::transport::Transport;
+use tokio::process::Command;
+
+const USAGE: &str = "\
+Usage: kcl-local [OPTIONS] [FILE]... -- <COMMAND> [ARG]...
+
+Runs COMMAND as MultiLang worker and sends it the lines of FILE (or stdin if
+none or `-` is given) as records. Checkpoints are persisted, so a restart
+resumes after the last checkpointed record.
+
+Options:
+      --shard-id <ID>         Shard id sent to the worker [default: shardId-000000000000]
+      --batch-size <N>        Records per processRecords action [default: 100]
+      --binary                Sends every FILE as a single record instead of its lines
+      --end <ACTION>          Sent once all records were delivered: shutdown,
+                              shutdown-requested, lease-lost, shard-ended or none
+                              [default: shard-ended]
+      --actions <FILE>        Reads an action (as for --end) from FILE, e.g. a named
+                              pipe, and sends it instead of the next batch
+      --checkpoints <DIR>     Directory the checkpoints are stored in [default: .kcl-local]
+  -h, --help                  Prints this help";
+
+struct Args {
+    options: LocalOptions,
+    checkpoints: PathBuf,
+    actions: Option<PathBuf>,
+    command: Command,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = LocalOptions {
+        sources: vec![],
+        ..LocalOptions::default()
+    };
+    let mut checkpoints = PathBuf::from(".kcl-local");
+    let mut actions = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--shard-id" => options.shard_id = value(&mut args, &arg)?,
+            "--batch-size" => {
+                options.batch_size = value(&mut args, &arg)?
+                    .parse()
+                    .map_err(|err| format!("Invalid batch size: {err}"))?;
+            }
+            "--binary" => options.format = Format::Binary,
+            "--end" => options.end = action(&value(&mut args, &arg)?)?,
+            "--actions" => actions = Some(value(&mut args, &arg)?.into()),
+            "--checkpoints" => checkpoints = value(&mut args, &arg)?.into(),
+            "-" => options.sources.push(Source::Stdin),
+            other if other.starts_with('-') => return Err(format!("Unknown option: {other}")),
+            file => options.sources.push(Source::File(file.into())),
+        }
+    }
+
+    if options.sources.is_empty() {
+        options.sources.push(Source::Stdin);
+    }
+
+    let program = args.next().ok_or("Missing worker command after `--`")?;
+    let mut command = Command::new(program);
+    command.args(args);
+
+    Ok(Args {
+        options,
+        checkpoints,
+        actions,
+        command,
+    })
+}
+
+fn action(name: &str) -> Result<Option<Simulate>, String> {
+    match name {
+        "shutdown" => Ok(Some(Simulate::Shutdown)),
+        "shutdown-requested" => Ok(Some(Simulate::ShutdownRequested)),
+        "lease-lost" => Ok(Some(Simulate::LeaseLost)),
+        "shard-ended" => Ok(Some(Simulate::ShardEnded)),
+        "none" => Ok(None),
+        other => Err(format!("Unknown action: {other}")),
+    }
+}
+
+/// Simulates the first action read from `path`; every action ends the run, so
+/// later ones are not read.
+///
+/// Runs on its own thread, as opening a named pipe blocks until it has a
+/// writer and must not keep the runtime from shutting down.
+fn read_actions(path: &Path, control: &LocalControl) {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Failed to open {}: {err}", path.display());
+            return;
+        }
+    };
+
+    for line in std::io::BufReader::new(file).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("Failed to read {}: {err}", path.display());
+                return;
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match action(line.trim()) {
+            Ok(Some(action)) => {
+                eprintln!("Simulating {action:?}");
+                control.simulate(action);
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+fn value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {name}"))
+}
+
+async fn run(args: Args) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    tokio::fs::create_dir_all(&args.checkpoints).await?;
+
+    // The transport only reads the checkpoint to skip records on start, the
+    // daemon answers the checkpoint requests of the worker
+    let (mut records, control) =
+        LocalTransport::new(args.options, FileSequenceStore::new(&args.checkpoints));
+
+    if let Some(path) = args.actions {
+        std::thread::spawn(move || read_actions(&path, &control));
+    }
+
+    let mut daemon = Daemon::spawn(args.command, FileSequenceStore::new(&args.checkpoints))?;
+
+    loop {
+        match records.read_message().await {
+            Ok(msg) => daemon.send(msg).await?,
+            Err(LocalError::Finished) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if let Some(checkpoint) = daemon.last_checkpoint() {
+        eprintln!("Last checkpoint: {checkpoint}");
+    }
+
+    let status = daemon.wait().await?;
+
+    Ok(match status.code() {
+        // Anything outside of 0..=255 would be truncated, possibly to 0
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE,
+    })
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,6 +1,12 @@
+use std::future::Future;
+use std::pin::pin;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot};
+
 use super::{
     message::input::Message as MessageIn, message::output::Message as MessageOut,
-    transport::Transport,
+    sequence::ExtendedSequenceNumber, transport::Transport,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -13,16 +19,83 @@ pub enum CheckpointError<TransportError> {
 
     #[error("invalid state: {}", message.id())]
     InvalidState { message: MessageIn },
+
+    #[error(transparent)]
+    HookFailed(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Errors of serving handle requests which end the run; failed checkpoints
+/// are reported to the requesting handle instead.
+#[derive(Debug)]
+pub(crate) enum ServeError<TransportError> {
+    TransportError(TransportError),
+    InvalidState { message: MessageIn },
+}
+
+impl<TransportError> From<ServeError<TransportError>> for CheckpointError<TransportError> {
+    fn from(err: ServeError<TransportError>) -> Self {
+        match err {
+            ServeError::TransportError(err) => Self::TransportError(err),
+            ServeError::InvalidState { message } => Self::InvalidState { message },
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointHandleError {
+    #[error("failed to checkpoint: {reason}")]
+    Failed { reason: String },
+
+    #[error("checkpointing is not possible during the current action")]
+    Unavailable,
+
+    #[error("checkpoint request was dropped")]
+    Dropped,
+}
+
+/// Runs alongside every checkpoint of a [`Checkpointer`] it was attached to
+/// with [`Checkpointer::with_hook`], e.g. to persist state at the same position.
+#[async_trait]
+pub trait CheckpointHook: Send {
+    /// Called before the checkpoint is sent; failing aborts the checkpoint.
+    async fn before_checkpoint(
+        &mut self,
+        position: &ExtendedSequenceNumber,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Called once the daemon confirmed the checkpoint.
+    async fn after_checkpoint(
+        &mut self,
+        position: &ExtendedSequenceNumber,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 }
 
-pub struct Checkpointer<'a, T>(&'a mut T);
+pub struct Checkpointer<'a, T> {
+    transport: &'a mut T,
+    queue: &'a mut CheckpointQueue,
+    hook: Option<&'a mut dyn CheckpointHook>,
+}
 
 impl<'a, T> Checkpointer<'a, T>
 where
     T: Transport,
 {
-    pub(crate) fn new(transport: &'a mut T) -> Self {
-        Self(transport)
+    pub(crate) fn new(transport: &'a mut T, queue: &'a mut CheckpointQueue) -> Self {
+        Self {
+            transport,
+            queue,
+            hook: None,
+        }
+    }
+
+    /// Returns a checkpointer which runs `hook` around every checkpoint,
+    /// including those requested through handles it serves.
+    pub fn with_hook<'b>(&'b mut self, hook: &'b mut dyn CheckpointHook) -> Checkpointer<'b, T> {
+        Checkpointer {
+            transport: &mut *self.transport,
+            queue: &mut *self.queue,
+            hook: Some(hook),
+        }
     }
 
     pub async fn checkpoint(
@@ -30,30 +103,259 @@ where
         sequence_number: Option<String>,
         sub_sequence_number: Option<u64>,
     ) -> Result<(), CheckpointError<T::Error>> {
-        let request = MessageOut::Checkpoint(super::message::output::CheckpointMessage {
+        checkpoint(
+            self.transport,
+            &mut self.queue.positions,
+            self.hook.as_deref_mut(),
             sequence_number,
             sub_sequence_number,
-        });
+        )
+        .await
+    }
+
+    /// Returns an owned handle which can checkpoint after this call has returned.
+    pub fn handle(&self) -> CheckpointHandle {
+        self.queue.handle()
+    }
 
-        self.0
-            .write_message(&request)
+    /// Awaits `fut` while performing checkpoints requested through handles.
+    ///
+    /// Handle requests are otherwise only performed after the current action
+    /// returned, so waiting on a task which itself waits on a handle must go
+    /// through this method.
+    pub async fn serve_until<F: Future>(
+        &mut self,
+        fut: F,
+    ) -> Result<F::Output, CheckpointError<T::Error>> {
+        self.queue
+            .serve_until(Some(&mut *self.transport), self.hook.as_deref_mut(), fut)
             .await
-            .map_err(CheckpointError::TransportError)?;
+            .map_err(CheckpointError::from)
+    }
+}
 
-        let response = self
-            .0
-            .read_message()
+async fn checkpoint<T: Transport>(
+    transport: &mut T,
+    positions: &mut Positions,
+    mut hook: Option<&mut (dyn CheckpointHook + '_)>,
+    sequence_number: Option<String>,
+    sub_sequence_number: Option<u64>,
+) -> Result<(), CheckpointError<T::Error>> {
+    let position = match &sequence_number {
+        Some(seq) => Some(ExtendedSequenceNumber::new(seq, sub_sequence_number)),
+        None => positions.delivered.clone(),
+    };
+
+    if let (Some(hook), Some(position)) = (hook.as_deref_mut(), &position) {
+        hook.before_checkpoint(position)
             .await
-            .map_err(CheckpointError::TransportError)?;
+            .map_err(CheckpointError::HookFailed)?;
+    }
+
+    let request = MessageOut::Checkpoint(super::message::output::CheckpointMessage {
+        sequence_number,
+        sub_sequence_number,
+    });
+
+    transport
+        .write_message(&request)
+        .await
+        .map_err(CheckpointError::TransportError)?;
+
+    let response = transport
+        .read_message()
+        .await
+        .map_err(CheckpointError::TransportError)?;
+
+    if let MessageIn::Checkpoint(msg) = response {
+        if let Some(error) = msg.error {
+            return Err(CheckpointError::Failed { reason: error });
+        }
+
+        positions.checkpointed.clone_from(&position);
+
+        if let (Some(hook), Some(position)) = (hook, &position) {
+            hook.after_checkpoint(position)
+                .await
+                .map_err(CheckpointError::HookFailed)?;
+        }
+
+        Ok(())
+    } else {
+        Err(CheckpointError::InvalidState { message: response })
+    }
+}
+
+#[derive(Debug)]
+struct CheckpointRequest {
+    sequence_number: Option<String>,
+    sub_sequence_number: Option<u64>,
+    reply: oneshot::Sender<Result<(), CheckpointHandleError>>,
+}
+
+/// Owned, cloneable checkpointer.
+///
+/// Requests are queued and performed by [`run`](crate::run) once the daemon
+/// accepts checkpoints, which is while a `processRecords`, `shutdown` or
+/// `shardEnded` action is outstanding. Awaiting a handle from within a
+/// processor callback blocks forever unless it happens through
+/// [`Checkpointer::serve_until`].
+#[derive(Debug, Clone)]
+pub struct CheckpointHandle {
+    requests: mpsc::UnboundedSender<CheckpointRequest>,
+
+    /// What a checkpoint without sequence number refers to instead of the last
+    /// record delivered by the daemon.
+    position: Option<ExtendedSequenceNumber>,
+}
+
+impl CheckpointHandle {
+    /// Without `sequence_number` checkpoints every record delivered so far,
+    /// or only up to the batch the handle was given for by a
+    /// [`Pipeline`](crate::pipeline::Pipeline).
+    pub async fn checkpoint(
+        &self,
+        sequence_number: Option<String>,
+        sub_sequence_number: Option<u64>,
+    ) -> Result<(), CheckpointHandleError> {
+        let (sequence_number, sub_sequence_number) = match (sequence_number, &self.position) {
+            (None, Some(position)) => (
+                Some(position.sequence_number.clone()),
+                position.sub_sequence_number,
+            ),
+            (sequence_number, _) => (sequence_number, sub_sequence_number),
+        };
+
+        let (reply, response) = oneshot::channel();
+
+        self.requests
+            .send(CheckpointRequest {
+                sequence_number,
+                sub_sequence_number,
+                reply,
+            })
+            .map_err(|_| CheckpointHandleError::Dropped)?;
+
+        response.await.map_err(|_| CheckpointHandleError::Dropped)?
+    }
+
+    /// Handle on which a checkpoint without sequence number refers to
+    /// `position` instead of the last delivered record.
+    pub(crate) fn up_to(&self, position: ExtendedSequenceNumber) -> Self {
+        Self {
+            requests: self.requests.clone(),
+            position: Some(position),
+        }
+    }
+}
+
+/// Positions of the shard as seen by the checkpointers.
+#[derive(Debug, Default)]
+pub(crate) struct Positions {
+    /// Last record delivered, which is what a checkpoint without sequence number refers to.
+    pub(crate) delivered: Option<ExtendedSequenceNumber>,
+    pub(crate) checkpointed: Option<ExtendedSequenceNumber>,
+}
+
+#[derive(Debug)]
+pub(crate) struct CheckpointQueue {
+    sender: mpsc::UnboundedSender<CheckpointRequest>,
+    receiver: mpsc::UnboundedReceiver<CheckpointRequest>,
+    pub(crate) positions: Positions,
+}
 
-        if let MessageIn::Checkpoint(msg) = response {
-            if let Some(error) = msg.error {
-                Err(CheckpointError::Failed { reason: error })
-            } else {
-                Ok(())
+impl CheckpointQueue {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        Self {
+            sender,
+            receiver,
+            positions: Positions::default(),
+        }
+    }
+
+    pub(crate) fn handle(&self) -> CheckpointHandle {
+        CheckpointHandle {
+            requests: self.sender.clone(),
+            position: None,
+        }
+    }
+
+    /// Performs all queued requests, or rejects them if no transport is given.
+    pub(crate) async fn serve_pending<T: Transport>(
+        &mut self,
+        mut transport: Option<&mut T>,
+    ) -> Result<(), ServeError<T::Error>> {
+        while let Ok(request) = self.receiver.try_recv() {
+            serve(request, transport.as_deref_mut(), &mut self.positions, None).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Awaits `fut` while performing incoming requests, or rejecting them if
+    /// no transport is given.
+    pub(crate) async fn serve_until<T, F>(
+        &mut self,
+        mut transport: Option<&mut T>,
+        mut hook: Option<&mut (dyn CheckpointHook + '_)>,
+        fut: F,
+    ) -> Result<F::Output, ServeError<T::Error>>
+    where
+        T: Transport,
+        F: Future,
+    {
+        let mut fut = pin!(fut);
+
+        loop {
+            tokio::select! {
+                biased;
+
+                Some(request) = self.receiver.recv() => {
+                    serve(request, transport.as_deref_mut(), &mut self.positions, hook.as_deref_mut()).await?;
+                }
+
+                output = &mut fut => return Ok(output),
             }
-        } else {
-            Err(CheckpointError::InvalidState { message: response })
         }
     }
 }
+
+async fn serve<T: Transport>(
+    request: CheckpointRequest,
+    transport: Option<&mut T>,
+    positions: &mut Positions,
+    hook: Option<&mut (dyn CheckpointHook + '_)>,
+) -> Result<(), ServeError<T::Error>> {
+    let Some(transport) = transport else {
+        let _ = request.reply.send(Err(CheckpointHandleError::Unavailable));
+        return Ok(());
+    };
+
+    let result = checkpoint(
+        transport,
+        positions,
+        hook,
+        request.sequence_number,
+        request.sub_sequence_number,
+    )
+    .await;
+
+    let reply = match result {
+        Ok(()) => Ok(()),
+        Err(CheckpointError::Failed { reason }) => Err(CheckpointHandleError::Failed { reason }),
+        Err(CheckpointError::HookFailed(err)) => Err(CheckpointHandleError::Failed {
+            reason: err.to_string(),
+        }),
+        // Requester only learns that its request was dropped
+        Err(CheckpointError::TransportError(err)) => return Err(ServeError::TransportError(err)),
+        Err(CheckpointError::InvalidState { message }) => {
+            return Err(ServeError::InvalidState { message });
+        }
+    };
+
+    let _ = request.reply.send(reply);
+
+    Ok(())
+}
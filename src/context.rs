@@ -0,0 +1,64 @@
+use std::time::SystemTime;
+
+use crate::checkpoint::CheckpointHandle;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Information about the shard being processed, maintained by [`run`](crate::run).
+#[derive(Debug, Clone)]
+pub struct ShardContext {
+    pub(crate) shard_id: String,
+    pub(crate) starting_sequence_number: Option<ExtendedSequenceNumber>,
+    pub(crate) worker_started_at: SystemTime,
+    pub(crate) batches: u64,
+    pub(crate) last_checkpoint: Option<ExtendedSequenceNumber>,
+    pub(crate) millis_behind_latest: Option<u64>,
+    pub(crate) checkpoint_handle: CheckpointHandle,
+}
+
+impl ShardContext {
+    pub(crate) fn new(checkpoint_handle: CheckpointHandle) -> Self {
+        Self {
+            shard_id: String::new(),
+            starting_sequence_number: None,
+            worker_started_at: SystemTime::now(),
+            batches: 0,
+            last_checkpoint: None,
+            millis_behind_latest: None,
+            checkpoint_handle,
+        }
+    }
+
+    /// Empty until the `initialize` action was received.
+    pub fn shard_id(&self) -> &str {
+        &self.shard_id
+    }
+
+    /// Position the daemon started the shard from, either the last checkpoint
+    /// or a sentinel like `TRIM_HORIZON`.
+    pub fn starting_sequence_number(&self) -> Option<&ExtendedSequenceNumber> {
+        self.starting_sequence_number.as_ref()
+    }
+
+    pub fn worker_started_at(&self) -> SystemTime {
+        self.worker_started_at
+    }
+
+    /// Number of `processRecords` actions received, including the current one.
+    pub fn batches(&self) -> u64 {
+        self.batches
+    }
+
+    /// Last successful checkpoint as of the start of the current action.
+    pub fn last_checkpoint(&self) -> Option<&ExtendedSequenceNumber> {
+        self.last_checkpoint.as_ref()
+    }
+
+    /// `millisBehindLatest` of the most recent batch.
+    pub fn millis_behind_latest(&self) -> Option<u64> {
+        self.millis_behind_latest
+    }
+
+    pub fn checkpoint_handle(&self) -> CheckpointHandle {
+        self.checkpoint_handle.clone()
+    }
+}
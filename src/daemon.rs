@@ -0,0 +1,260 @@
+use std::fmt;
+use std::process::ExitStatus;
+
+use tokio::process::Command;
+
+use crate::dedup::SequenceStore;
+use crate::message::input::{
+    CheckpointMessage, InitializeMessage, Message as MessageIn, ProcessRecordsMessage, Record,
+};
+use crate::message::output::{self, Message as MessageOut};
+use crate::sequence::{ExtendedSequenceNumber, SHARD_END, TRIM_HORIZON};
+use crate::worker::WorkerProcess;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DaemonError<StoreError> {
+    #[error(transparent)]
+    StoreError(StoreError),
+
+    #[error("expected status for {expected}, got one for {actual}")]
+    UnexpectedStatus {
+        expected: &'static str,
+        actual: String,
+    },
+
+    #[error("worker closed its output")]
+    Closed,
+
+    #[error(transparent)]
+    IoError(std::io::Error),
+}
+
+/// Drives a MultiLang worker process the way the Java MultiLangDaemon does.
+///
+/// Each action is written to the stdin of the worker, after which its stdout
+/// is read until the matching status arrives. Checkpoint requests in between
+/// are persisted to the [`SequenceStore`], its errors are reported back to
+/// the worker in the checkpoint reply.
+#[derive(Debug)]
+pub struct Daemon<S> {
+    process: WorkerProcess,
+    store: S,
+    shard_id: String,
+    checkpoints: Checkpoints,
+}
+
+impl<S> Daemon<S>
+where
+    S: SequenceStore,
+    S::Error: fmt::Display,
+{
+    /// Spawns `command` with piped stdin and stdout; stderr is inherited.
+    pub fn spawn(mut command: Command, store: S) -> std::io::Result<Self> {
+        Ok(Self {
+            process: WorkerProcess::spawn(&mut command)?,
+            store,
+            shard_id: String::new(),
+            checkpoints: Checkpoints::default(),
+        })
+    }
+
+    pub fn last_checkpoint(&self) -> Option<&ExtendedSequenceNumber> {
+        self.checkpoints.last.as_ref()
+    }
+
+    /// Sends `initialize`, starting after the stored checkpoint or at
+    /// `TRIM_HORIZON`.
+    pub async fn initialize(
+        &mut self,
+        shard_id: impl Into<String>,
+    ) -> Result<(), DaemonError<S::Error>> {
+        let shard_id = shard_id.into();
+        let start = self
+            .store
+            .load(&shard_id)
+            .await
+            .map_err(DaemonError::StoreError)?
+            .unwrap_or_else(|| ExtendedSequenceNumber::new(TRIM_HORIZON, None));
+
+        self.send(MessageIn::Initialize(InitializeMessage {
+            shard_id,
+            sequence_number: Some(start.sequence_number),
+            sub_sequence_number: start.sub_sequence_number,
+        }))
+        .await
+    }
+
+    pub async fn process_records(
+        &mut self,
+        records: Vec<Record>,
+        millis_behind_latest: Option<u64>,
+    ) -> Result<(), DaemonError<S::Error>> {
+        self.send(MessageIn::ProcessRecords(ProcessRecordsMessage {
+            records,
+            millis_behind_latest,
+        }))
+        .await
+    }
+
+    /// Writes `msg` and answers checkpoint requests until the worker
+    /// acknowledged it.
+    pub async fn send(&mut self, msg: MessageIn) -> Result<(), DaemonError<S::Error>> {
+        if let MessageIn::Initialize(m) = &msg {
+            self.shard_id.clone_from(&m.shard_id);
+        }
+
+        self.checkpoints.sent(&msg);
+
+        let expected = msg.id();
+        self.process
+            .write(&msg)
+            .await
+            .map_err(DaemonError::IoError)?;
+
+        loop {
+            let msg = self
+                .process
+                .read()
+                .await
+                .map_err(DaemonError::IoError)?
+                .ok_or(DaemonError::Closed)?;
+
+            match msg {
+                MessageOut::Status(status) if status.response_for == expected => return Ok(()),
+                MessageOut::Status(status) => {
+                    return Err(DaemonError::UnexpectedStatus {
+                        expected,
+                        actual: status.response_for,
+                    });
+                }
+                MessageOut::Checkpoint(m) => {
+                    let reply = self
+                        .checkpoints
+                        .request(&mut self.store, &self.shard_id, &m)
+                        .await;
+
+                    self.process
+                        .write(&reply)
+                        .await
+                        .map_err(DaemonError::IoError)?;
+                }
+            }
+        }
+    }
+
+    /// Closes stdin and waits for the worker to exit.
+    pub async fn wait(self) -> std::io::Result<ExitStatus> {
+        self.process.wait().await
+    }
+
+    pub async fn kill(mut self) -> std::io::Result<()> {
+        self.process.child.kill().await
+    }
+}
+
+/// Checkpoint bookkeeping of the daemon side, shared with
+/// [`LocalTransport`](crate::local::LocalTransport).
+///
+/// A checkpoint without a sequence number means the last delivered record, or
+/// `SHARD_END` once `shardEnded` was sent. Like the daemon, checkpoints past
+/// the delivered records or before the last checkpoint are rejected.
+#[derive(Debug, Default)]
+pub(crate) struct Checkpoints {
+    delivered: Option<ExtendedSequenceNumber>,
+    pub(crate) last: Option<ExtendedSequenceNumber>,
+    lease_lost: bool,
+}
+
+impl Checkpoints {
+    /// Updates what can be checkpointed once `msg` was sent to the worker.
+    pub(crate) fn sent(&mut self, msg: &MessageIn) {
+        match msg {
+            MessageIn::Initialize(m) => {
+                self.delivered = m
+                    .extended_sequence_number()
+                    .filter(|seq| !seq.is_sentinel());
+                self.last.clone_from(&self.delivered);
+                self.lease_lost = false;
+            }
+            MessageIn::ProcessRecords(m) => {
+                if let Some(record) = m.records.last() {
+                    self.delivered = Some(record.extended_sequence_number());
+                }
+            }
+            MessageIn::ShardEnded(_) => {
+                self.delivered = Some(ExtendedSequenceNumber::new(SHARD_END, None));
+            }
+            MessageIn::LeaseLost(_) => self.lease_lost = true,
+            _ => {}
+        }
+    }
+
+    /// Persists the checkpoint requested by the worker and returns the reply.
+    pub(crate) async fn request<S>(
+        &mut self,
+        store: &mut S,
+        shard_id: &str,
+        msg: &output::CheckpointMessage,
+    ) -> MessageIn
+    where
+        S: SequenceStore,
+        S::Error: fmt::Display,
+    {
+        let requested = match &msg.sequence_number {
+            Some(seq) => Some(ExtendedSequenceNumber::new(seq, msg.sub_sequence_number)),
+            None => self.delivered.clone(),
+        };
+
+        let error = self.checkpoint(store, shard_id, requested.as_ref()).await;
+
+        MessageIn::Checkpoint(CheckpointMessage {
+            sequence_number: requested.as_ref().map(|seq| seq.sequence_number.clone()),
+            sub_sequence_number: requested.and_then(|seq| seq.sub_sequence_number),
+            error,
+        })
+    }
+
+    /// Returns the error to reply with, if any.
+    async fn checkpoint<S>(
+        &mut self,
+        store: &mut S,
+        shard_id: &str,
+        requested: Option<&ExtendedSequenceNumber>,
+    ) -> Option<String>
+    where
+        S: SequenceStore,
+        S::Error: fmt::Display,
+    {
+        if self.lease_lost {
+            return Some("ShutdownException: lease was lost".into());
+        }
+
+        let Some(requested) = requested else {
+            return Some("InvalidStateException: no records were delivered yet".into());
+        };
+
+        if self
+            .delivered
+            .as_ref()
+            .is_none_or(|delivered| requested > delivered)
+        {
+            return Some(format!(
+                "InvalidStateException: sequence number {requested} was not delivered yet"
+            ));
+        }
+
+        if let Some(last) = self.last.as_ref().filter(|last| requested < *last) {
+            return Some(format!(
+                "InvalidStateException: sequence number {requested} is before the last checkpoint {last}"
+            ));
+        }
+
+        match store.store(shard_id, requested).await {
+            Ok(()) => {
+                self.last = Some(requested.clone());
+                None
+            }
+            Err(err) => Some(format!("KinesisClientLibDependencyException: {err}")),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::checkpoint::Checkpointer;
+use crate::context::ShardContext;
+use crate::message::input::{
+    InitializeMessage, LeaseLostMessage, ProcessRecordsMessage, ShardEndedMessage, ShutdownMessage,
+    ShutdownRequestedMessage,
+};
+use crate::processor::Processor;
+use crate::sequence::ExtendedSequenceNumber;
+use crate::transport::Transport;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DedupError<ProcessorError, StoreError> {
+    #[error(transparent)]
+    ProcessorError(ProcessorError),
+
+    #[error(transparent)]
+    StoreError(StoreError),
+}
+
+/// Persists the last processed sequence number of each shard.
+#[async_trait]
+pub trait SequenceStore: Send {
+    type Error;
+
+    async fn load(&mut self, shard_id: &str)
+    -> Result<Option<ExtendedSequenceNumber>, Self::Error>;
+
+    async fn store(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Keeps sequence numbers for the lifetime of the process only.
+#[derive(Debug, Default)]
+pub struct MemorySequenceStore(HashMap<String, ExtendedSequenceNumber>);
+
+#[async_trait]
+impl SequenceStore for MemorySequenceStore {
+    type Error = std::convert::Infallible;
+
+    async fn load(
+        &mut self,
+        shard_id: &str,
+    ) -> Result<Option<ExtendedSequenceNumber>, Self::Error> {
+        Ok(self.0.get(shard_id).cloned())
+    }
+
+    async fn store(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<(), Self::Error> {
+        self.0.insert(shard_id.to_owned(), sequence_number.clone());
+        Ok(())
+    }
+}
+
+/// Stores the sequence number of each shard in a file named after the shard id.
+#[derive(Debug, Clone)]
+pub struct FileSequenceStore {
+    directory: PathBuf,
+}
+
+impl FileSequenceStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SequenceStore for FileSequenceStore {
+    type Error = std::io::Error;
+
+    async fn load(
+        &mut self,
+        shard_id: &str,
+    ) -> Result<Option<ExtendedSequenceNumber>, Self::Error> {
+        let content = match tokio::fs::read_to_string(self.directory.join(shard_id)).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        content
+            .parse()
+            .map(Some)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    async fn store(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<(), Self::Error> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+
+        crate::fs::write_atomic(
+            &self.directory.join(shard_id),
+            sequence_number.to_string().as_bytes(),
+        )
+        .await
+    }
+}
+
+/// [`Processor`] which drops records that were already processed before.
+///
+/// After a worker restart the daemon redelivers all records since the last
+/// checkpoint. This remembers the highest sequence number of every batch the
+/// inner processor handled successfully and filters out records up to it.
+#[derive(Debug)]
+pub struct Dedup<P, S> {
+    inner: P,
+    store: S,
+    last: Option<ExtendedSequenceNumber>,
+}
+
+impl<P, S> Dedup<P, S> {
+    pub fn new(inner: P, store: S) -> Self {
+        Self {
+            inner,
+            store,
+            last: None,
+        }
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<T, P, S> Processor<T> for Dedup<P, S>
+where
+    T: Transport + Send,
+    P: Processor<T> + Send,
+    S: SequenceStore,
+{
+    type Error = DedupError<P::Error, S::Error>;
+
+    async fn initialize(
+        &mut self,
+        ctx: &ShardContext,
+        msg: InitializeMessage,
+    ) -> Result<(), Self::Error> {
+        self.last = self
+            .store
+            .load(ctx.shard_id())
+            .await
+            .map_err(DedupError::StoreError)?;
+
+        self.inner
+            .initialize(ctx, msg)
+            .await
+            .map_err(DedupError::ProcessorError)
+    }
+
+    async fn process_records(
+        &mut self,
+        ctx: &ShardContext,
+        mut msg: ProcessRecordsMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        if let Some(last) = &self.last {
+            msg.records
+                .retain(|record| record.extended_sequence_number() > *last);
+        }
+
+        let highest = msg
+            .records
+            .iter()
+            .map(|record| record.extended_sequence_number())
+            .max();
+
+        self.inner
+            .process_records(ctx, msg, checkpointer)
+            .await
+            .map_err(DedupError::ProcessorError)?;
+
+        if let Some(highest) = highest {
+            self.store
+                .store(ctx.shard_id(), &highest)
+                .await
+                .map_err(DedupError::StoreError)?;
+            self.last = Some(highest);
+        }
+
+        Ok(())
+    }
+
+    async fn shutdown(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .shutdown(ctx, msg, checkpointer)
+            .await
+            .map_err(DedupError::ProcessorError)
+    }
+
+    async fn shutdown_requested(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownRequestedMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .shutdown_requested(ctx, msg, checkpointer)
+            .await
+            .map_err(DedupError::ProcessorError)
+    }
+
+    async fn lease_lost(
+        &mut self,
+        ctx: &ShardContext,
+        msg: LeaseLostMessage,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .lease_lost(ctx, msg)
+            .await
+            .map_err(DedupError::ProcessorError)
+    }
+
+    async fn shard_ended(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShardEndedMessage,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .shard_ended(ctx, msg)
+            .await
+            .map_err(DedupError::ProcessorError)
+    }
+}
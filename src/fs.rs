@@ -0,0 +1,27 @@
+use std::path::Path;
+
+use tokio::io::AsyncWriteExt;
+
+/// Replaces the file at `path` with `data`, so a crash leaves either the old or
+/// the new content.
+///
+/// The data is written and synced to a hidden temporary file next to `path`
+/// first, which is then renamed. On Unix, the directory is synced as well to
+/// persist the rename itself; directories cannot be opened on Windows.
+pub(crate) async fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = directory.join(format!(".{file_name}.tmp"));
+
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp_path, path).await?;
+
+    #[cfg(unix)]
+    tokio::fs::File::open(directory).await?.sync_all().await?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::io::Write;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+#[cfg(not(any(feature = "simd-json", feature = "serde_json", feature = "sonic-rs")))]
+compile_error!("one of the `simd-json`, `serde_json` or `sonic-rs` features must be enabled");
+
+/// JSON serialization used by all transports.
+///
+/// The backend is selected through cargo features: `simd-json` is the
+/// default, `sonic-rs` takes precedence if enabled and `serde_json` over both
+/// for platforms or Miri runs where neither can be used.
+pub trait JsonBackend {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Parses `buf`, which may be used as scratch space and is left modified.
+    fn from_slice<T: DeserializeOwned>(buf: &mut [u8]) -> Result<T, Self::Error>;
+
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Self::Error>;
+
+    fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "simd-json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimdJson;
+
+#[cfg(feature = "simd-json")]
+impl JsonBackend for SimdJson {
+    type Error = simd_json::Error;
+
+    fn from_slice<T: DeserializeOwned>(buf: &mut [u8]) -> Result<T, Self::Error> {
+        simd_json::from_slice(buf)
+    }
+
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        simd_json::to_vec(value)
+    }
+
+    fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Self::Error> {
+        simd_json::to_string(value)
+    }
+
+    fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Self::Error> {
+        simd_json::to_writer(writer, value)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerdeJson;
+
+#[cfg(feature = "serde_json")]
+impl JsonBackend for SerdeJson {
+    type Error = serde_json::Error;
+
+    fn from_slice<T: DeserializeOwned>(buf: &mut [u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(buf)
+    }
+
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Self::Error> {
+        serde_json::to_string(value)
+    }
+
+    fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Self::Error> {
+        serde_json::to_writer(writer, value)
+    }
+}
+
+#[cfg(feature = "sonic-rs")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SonicRs;
+
+#[cfg(feature = "sonic-rs")]
+impl JsonBackend for SonicRs {
+    type Error = sonic_rs::Error;
+
+    fn from_slice<T: DeserializeOwned>(buf: &mut [u8]) -> Result<T, Self::Error> {
+        sonic_rs::from_slice(buf)
+    }
+
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        sonic_rs::to_vec(value)
+    }
+
+    fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Self::Error> {
+        sonic_rs::to_string(value)
+    }
+
+    fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Self::Error> {
+        sonic_rs::to_writer(sonic_rs::writer::BufferedWriter::new(writer), value)
+    }
+}
+
+/// Backend selected by the enabled features.
+#[cfg(feature = "serde_json")]
+pub type Json = SerdeJson;
+
+/// Backend selected by the enabled features.
+#[cfg(all(feature = "sonic-rs", not(feature = "serde_json")))]
+pub type Json = SonicRs;
+
+/// Backend selected by the enabled features.
+#[cfg(all(
+    feature = "simd-json",
+    not(any(feature = "serde_json", feature = "sonic-rs"))
+))]
+pub type Json = SimdJson;
+
+pub type Error = <Json as JsonBackend>::Error;
+
+pub fn from_slice<T: DeserializeOwned>(buf: &mut [u8]) -> Result<T, Error> {
+    Json::from_slice(buf)
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    Json::to_vec(value)
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    Json::to_string(value)
+}
+
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Error> {
+    Json::to_writer(writer, value)
+}
@@ -1,18 +1,42 @@
-use checkpoint::Checkpointer;
+use checkpoint::{CheckpointQueue, Checkpointer, ServeError};
+use context::ShardContext;
 use message::input::Message as MessageIn;
 use message::output::Message as MessageOut;
 use processor::Processor;
+use protocol::{ProtocolState, ProtocolVersion, ProtocolViolation, Validation};
 use transport::Transport;
 
 pub mod checkpoint;
+pub mod context;
+#[cfg(feature = "local")]
+pub mod daemon;
+pub mod dedup;
+mod fs;
+pub mod json;
+#[cfg(feature = "local")]
+pub mod local;
 pub mod message;
+pub mod parallel;
+pub mod pipeline;
 pub mod processor;
+pub mod protocol;
+pub mod replay;
+pub mod sequence;
+pub mod state;
+#[cfg(feature = "supervisor")]
+pub mod supervisor;
 pub mod transport;
+pub mod window;
+#[cfg(any(feature = "local", feature = "supervisor"))]
+mod worker;
 
 #[derive(Debug, thiserror::Error)]
 pub enum RunError<IoError, ProcessorError> {
     UnexpectedMessage(MessageIn),
 
+    #[error(transparent)]
+    ProtocolViolation(ProtocolViolation),
+
     #[error(transparent)]
     IoError(IoError),
 
@@ -20,30 +44,99 @@ pub enum RunError<IoError, ProcessorError> {
     ProcessorError(ProcessorError),
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub validation: Validation,
+    pub protocol_version: ProtocolVersion,
+}
+
 pub async fn run<T: Transport + Send, P: Processor<T>>(
+    transport: T,
+    processor: P,
+) -> Result<(), RunError<T::Error, P::Error>> {
+    run_with_options(transport, processor, RunOptions::default()).await
+}
+
+pub async fn run_with_options<T: Transport + Send, P: Processor<T>>(
     mut transport: T,
     mut processor: P,
+    options: RunOptions,
 ) -> Result<(), RunError<T::Error, P::Error>> {
+    let mut queue = CheckpointQueue::new();
+    let mut ctx = ShardContext::new(queue.handle());
+    let mut protocol = ProtocolState::new();
+
     loop {
         let msg = transport.read_message().await.map_err(RunError::IoError)?;
+        let response_for = msg.id();
+
+        if options.validation != Validation::Disabled {
+            // Validate message
+
+            for violation in protocol.validate(&msg) {
+                if options.validation == Validation::Strict {
+                    return Err(RunError::ProtocolViolation(violation));
+                }
+
+                transport
+                    .write_error(&format!("protocol violation: {violation}"))
+                    .await
+                    .map_err(RunError::IoError)?;
+            }
+        }
+
+        // KCL 1.x reports lease loss and shard end as `shutdown`
+        let msg = options.protocol_version.translate(msg);
         let msg_id = msg.id();
 
         {
-            // Handle message
+            // Update context
 
-            let mut checkpointer = Checkpointer::new(&mut transport);
+            match &msg {
+                MessageIn::Initialize(m) => {
+                    ctx.shard_id = m.shard_id.clone();
+                    ctx.starting_sequence_number = m.extended_sequence_number();
+                }
+                MessageIn::ProcessRecords(m) => {
+                    ctx.batches += 1;
+                    ctx.millis_behind_latest = m.millis_behind_latest;
+
+                    if let Some(record) = m.records.last() {
+                        queue.positions.delivered = Some(record.extended_sequence_number());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        {
+            // Handle message
 
             match msg {
-                MessageIn::Initialize(m) => processor.initialize(m).await,
+                MessageIn::Initialize(m) => processor.initialize(&ctx, m).await,
                 MessageIn::ProcessRecords(m) => {
-                    processor.process_records(m, &mut checkpointer).await
+                    let mut checkpointer = Checkpointer::new(&mut transport, &mut queue);
+                    processor.process_records(&ctx, m, &mut checkpointer).await
+                }
+                MessageIn::Shutdown(m) => {
+                    let mut checkpointer = Checkpointer::new(&mut transport, &mut queue);
+                    processor.shutdown(&ctx, m, &mut checkpointer).await
                 }
-                MessageIn::Shutdown(m) => processor.shutdown(m, &mut checkpointer).await,
                 MessageIn::ShutdownRequested(m) => {
-                    processor.shutdown_requested(m, &mut checkpointer).await
+                    let mut checkpointer = Checkpointer::new(&mut transport, &mut queue);
+                    processor
+                        .shutdown_requested(&ctx, m, &mut checkpointer)
+                        .await
                 }
-                MessageIn::LeaseLost(m) => processor.lease_lost(m).await,
-                MessageIn::ShardEnded(m) => processor.shard_ended(m).await,
+                // Checkpoints are no longer possible once the lease is gone
+                MessageIn::LeaseLost(m) => queue
+                    .serve_until(None::<&mut T>, None, processor.lease_lost(&ctx, m))
+                    .await
+                    .map_err(checkpoint_error)?,
+                MessageIn::ShardEnded(m) => queue
+                    .serve_until(Some(&mut transport), None, processor.shard_ended(&ctx, m))
+                    .await
+                    .map_err(checkpoint_error)?,
 
                 msg => {
                     return Err(RunError::UnexpectedMessage(msg));
@@ -52,11 +145,27 @@ pub async fn run<T: Transport + Send, P: Processor<T>>(
             .map_err(RunError::ProcessorError)?;
         }
 
+        {
+            // Perform checkpoints requested through handles
+
+            let served = match msg_id {
+                // Daemon does not accept checkpoints yet; keep them queued
+                "initialize" => Ok(()),
+                "leaseLost" => queue.serve_pending(None::<&mut T>).await,
+                _ => queue.serve_pending(Some(&mut transport)).await,
+            };
+
+            served.map_err(checkpoint_error)?;
+
+            ctx.last_checkpoint
+                .clone_from(&queue.positions.checkpointed);
+        }
+
         {
             // Acknowledge message
 
             let response = MessageOut::Status(message::output::StatusMessage {
-                response_for: msg_id.into(),
+                response_for: response_for.into(),
             });
 
             transport
@@ -66,3 +175,12 @@ pub async fn run<T: Transport + Send, P: Processor<T>>(
         }
     }
 }
+
+fn checkpoint_error<IoError, ProcessorError>(
+    err: ServeError<IoError>,
+) -> RunError<IoError, ProcessorError> {
+    match err {
+        ServeError::TransportError(err) => RunError::IoError(err),
+        ServeError::InvalidState { message } => RunError::UnexpectedMessage(message),
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::prelude::*;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::daemon::Checkpoints;
+use crate::dedup::SequenceStore;
+use crate::message::input::{
+    InitializeMessage, LeaseLostMessage, Message as MessageIn, ProcessRecordsMessage, Record,
+    ShardEndedMessage, ShutdownMessage, ShutdownRequestedMessage,
+};
+use crate::message::output::Message as MessageOut;
+use crate::processor::Processor;
+use crate::sequence::{ExtendedSequenceNumber, SHARD_END, TRIM_HORIZON};
+use crate::transport::Transport;
+use crate::{RunError, RunOptions};
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocalError<StoreError> {
+    #[error(transparent)]
+    StoreError(StoreError),
+
+    #[error("all records were delivered")]
+    Finished,
+
+    #[error(transparent)]
+    IoError(std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Every non-empty line (e.g. of a JSONL/NDJSON file) is one record.
+    #[default]
+    Lines,
+
+    /// Every source is one record.
+    Binary,
+}
+
+/// Daemon action to send instead of further records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simulate {
+    Shutdown,
+
+    /// Followed by `leaseLost`, like a graceful shutdown of the KCL worker.
+    ShutdownRequested,
+
+    LeaseLost,
+    ShardEnded,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalOptions {
+    pub shard_id: String,
+    pub sources: Vec<Source>,
+    pub format: Format,
+    pub batch_size: usize,
+
+    /// Sent once all sources are exhausted; `None` stops without notifying
+    /// the processor.
+    pub end: Option<Simulate>,
+}
+
+impl Default for LocalOptions {
+    fn default() -> Self {
+        Self {
+            shard_id: "shardId-000000000000".into(),
+            sources: vec![Source::Stdin],
+            format: Format::default(),
+            batch_size: 100,
+            end: Some(Simulate::ShardEnded),
+        }
+    }
+}
+
+/// Requests simulated daemon actions while [`LocalTransport`] is running.
+#[derive(Debug, Clone)]
+pub struct LocalControl {
+    sender: mpsc::UnboundedSender<Simulate>,
+}
+
+impl LocalControl {
+    /// Sent before the next batch; records not delivered yet are dropped.
+    ///
+    /// Returns `false` if the transport is gone.
+    pub fn simulate(&self, action: Simulate) -> bool {
+        self.sender.send(action).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Initialize,
+    Records,
+    Ending(Simulate),
+    Ended,
+}
+
+/// Plays the daemon side of the protocol in-process, without Kinesis or Java.
+///
+/// Records are read from the sources and numbered starting at `1`, so reading
+/// the same input again yields the same sequence numbers. Checkpoints are
+/// answered like [`Daemon`](crate::daemon::Daemon) does and the shard resumes
+/// after the last checkpoint on the next start. Once done, reading fails with
+/// [`LocalError::Finished`].
+pub struct LocalTransport<S> {
+    options: LocalOptions,
+    store: S,
+    sources: VecDeque<Source>,
+    reader: Option<Box<dyn AsyncBufRead + Send + Unpin>>,
+    phase: Phase,
+    next_sequence_number: u64,
+    checkpoints: Checkpoints,
+    replies: VecDeque<MessageIn>,
+    receiver: mpsc::UnboundedReceiver<Simulate>,
+}
+
+impl<S: SequenceStore> LocalTransport<S> {
+    pub fn new(options: LocalOptions, store: S) -> (Self, LocalControl) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let transport = Self {
+            sources: options.sources.iter().cloned().collect(),
+            options,
+            store,
+            reader: None,
+            phase: Phase::Initialize,
+            next_sequence_number: 1,
+            checkpoints: Checkpoints::default(),
+            replies: VecDeque::new(),
+            receiver,
+        };
+
+        (transport, LocalControl { sender })
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    async fn next_data(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(reader) = &mut self.reader {
+                let mut data = vec![];
+
+                match self.options.format {
+                    Format::Lines => {
+                        if reader.read_until(b'\n', &mut data).await? == 0 {
+                            self.reader = None;
+                            continue;
+                        }
+
+                        if data.trim_ascii().is_empty() {
+                            continue;
+                        }
+
+                        if data.ends_with(b"\n") {
+                            data.pop();
+                        }
+
+                        if data.ends_with(b"\r") {
+                            data.pop();
+                        }
+                    }
+                    Format::Binary => {
+                        reader.read_to_end(&mut data).await?;
+                        self.reader = None;
+                    }
+                }
+
+                return Ok(Some(data));
+            }
+
+            let Some(source) = self.sources.pop_front() else {
+                return Ok(None);
+            };
+
+            self.reader = Some(match source {
+                Source::Stdin => Box::new(BufReader::new(tokio::io::stdin())),
+                Source::File(path) => Box::new(BufReader::new(tokio::fs::File::open(path).await?)),
+            });
+        }
+    }
+
+    async fn next_batch(&mut self) -> std::io::Result<Vec<Record>> {
+        let mut records = vec![];
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        while records.len() < self.options.batch_size.max(1) {
+            let Some(data) = self.next_data().await? else {
+                break;
+            };
+
+            let sequence_number =
+                ExtendedSequenceNumber::new(self.next_sequence_number.to_string(), None);
+            self.next_sequence_number += 1;
+
+            // Records up to the checkpoint were processed by an earlier run
+            if self
+                .checkpoints
+                .last
+                .as_ref()
+                .is_some_and(|checkpoint| *checkpoint >= sequence_number)
+            {
+                continue;
+            }
+
+            records.push(Record {
+                base64_data: BASE64_STANDARD.encode(&data),
+                partition_key: sequence_number.sequence_number.clone(),
+                sequence_number: sequence_number.sequence_number,
+                sub_sequence_number: None,
+                approximate_arrival_timestamp_ms: Some(timestamp),
+                explicit_hash_key: None,
+                encryption_type: None,
+            });
+        }
+
+        Ok(records)
+    }
+
+    async fn next_message(&mut self) -> Result<MessageIn, LocalError<S::Error>> {
+        if self.phase == Phase::Records
+            && let Ok(action) = self.receiver.try_recv()
+        {
+            return Ok(self.end(action));
+        }
+
+        match self.phase {
+            Phase::Initialize => {
+                let checkpoint = self
+                    .store
+                    .load(&self.options.shard_id)
+                    .await
+                    .map_err(LocalError::StoreError)?;
+
+                if checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.sequence_number == SHARD_END)
+                {
+                    // Shard was completed by an earlier run
+                    self.phase = Phase::Ended;
+                    return Err(LocalError::Finished);
+                }
+
+                self.phase = Phase::Records;
+
+                let start =
+                    checkpoint.unwrap_or_else(|| ExtendedSequenceNumber::new(TRIM_HORIZON, None));
+
+                Ok(MessageIn::Initialize(InitializeMessage {
+                    shard_id: self.options.shard_id.clone(),
+                    sequence_number: Some(start.sequence_number),
+                    sub_sequence_number: start.sub_sequence_number,
+                }))
+            }
+            Phase::Records => {
+                let records = self.next_batch().await.map_err(LocalError::IoError)?;
+
+                if records.is_empty() {
+                    return match self.options.end {
+                        Some(action) => Ok(self.end(action)),
+                        None => {
+                            self.phase = Phase::Ended;
+                            Err(LocalError::Finished)
+                        }
+                    };
+                }
+
+                Ok(MessageIn::ProcessRecords(ProcessRecordsMessage {
+                    records,
+                    millis_behind_latest: Some(0),
+                }))
+            }
+            Phase::Ending(action) => Ok(self.end(action)),
+            Phase::Ended => Err(LocalError::Finished),
+        }
+    }
+
+    fn end(&mut self, action: Simulate) -> MessageIn {
+        self.phase = match action {
+            Simulate::ShutdownRequested => Phase::Ending(Simulate::LeaseLost),
+            _ => Phase::Ended,
+        };
+
+        match action {
+            Simulate::Shutdown => MessageIn::Shutdown(ShutdownMessage { reason: None }),
+            Simulate::ShutdownRequested => {
+                MessageIn::ShutdownRequested(ShutdownRequestedMessage {})
+            }
+            Simulate::LeaseLost => MessageIn::LeaseLost(LeaseLostMessage {}),
+            Simulate::ShardEnded => MessageIn::ShardEnded(ShardEndedMessage {}),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Transport for LocalTransport<S>
+where
+    S: SequenceStore,
+    S::Error: Send + std::fmt::Display,
+{
+    type Error = LocalError<S::Error>;
+
+    async fn write_error(&mut self, error: &str) -> Result<(), Self::Error> {
+        let mut stderr = tokio::io::stderr();
+
+        stderr
+            .write_all(format!("{error}\n").as_bytes())
+            .await
+            .map_err(LocalError::IoError)
+    }
+
+    async fn write_message(&mut self, message: &MessageOut) -> Result<(), Self::Error> {
+        let MessageOut::Checkpoint(m) = message else {
+            return Ok(());
+        };
+
+        let reply = self
+            .checkpoints
+            .request(&mut self.store, &self.options.shard_id, m)
+            .await;
+
+        self.replies.push_back(reply);
+
+        Ok(())
+    }
+
+    async fn read_message(&mut self) -> Result<MessageIn, Self::Error> {
+        if let Some(reply) = self.replies.pop_front() {
+            return Ok(reply);
+        }
+
+        let msg = self.next_message().await?;
+        self.checkpoints.sent(&msg);
+
+        Ok(msg)
+    }
+}
+
+/// Runs `processor` against `transport` until all records were delivered and
+/// the final action (if any) was acknowledged.
+///
+/// Actions are simulated through the [`LocalControl`] returned alongside the
+/// transport by [`LocalTransport::new`].
+pub async fn run_local<P, S>(
+    transport: LocalTransport<S>,
+    processor: P,
+) -> Result<(), RunError<LocalError<S::Error>, P::Error>>
+where
+    P: Processor<LocalTransport<S>>,
+    S: SequenceStore,
+    S::Error: Send + std::fmt::Display,
+{
+    match crate::run_with_options(transport, processor, RunOptions::default()).await {
+        Ok(()) | Err(RunError::IoError(LocalError::Finished)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
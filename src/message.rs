@@ -1,7 +1,7 @@
 pub mod output {
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(tag = "action")]
     pub enum Message {
         #[serde(rename = "checkpoint")]
@@ -11,7 +11,7 @@ pub mod output {
         Status(StatusMessage),
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct CheckpointMessage {
         #[serde(rename = "sequenceNumber")]
         pub sequence_number: Option<String>,
@@ -24,7 +24,7 @@ pub mod output {
         pub sub_sequence_number: Option<u64>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct StatusMessage {
         #[serde(rename = "responseFor")]
         pub response_for: String,
@@ -40,10 +40,15 @@ pub mod output {
 }
 
 pub mod input {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
     use base64::prelude::*;
+    use md5::{Digest, Md5};
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Serialize, Deserialize)]
+    use crate::sequence::ExtendedSequenceNumber;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(tag = "action")]
     pub enum Message {
         #[serde(rename = "checkpoint")]
@@ -82,7 +87,7 @@ pub mod input {
         }
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct CheckpointMessage {
         #[serde(rename = "sequenceNumber")]
         pub sequence_number: Option<String>,
@@ -98,7 +103,7 @@ pub mod input {
         pub error: Option<String>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct InitializeMessage {
         #[serde(rename = "shardId")]
         pub shard_id: String,
@@ -118,7 +123,15 @@ pub mod input {
         pub sub_sequence_number: Option<u64>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    impl InitializeMessage {
+        pub fn extended_sequence_number(&self) -> Option<ExtendedSequenceNumber> {
+            self.sequence_number
+                .as_ref()
+                .map(|seq| ExtendedSequenceNumber::new(seq, self.sub_sequence_number))
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Record {
         #[serde(rename = "data")]
         pub base64_data: String,
@@ -142,18 +155,58 @@ pub mod input {
             default
         )]
         pub approximate_arrival_timestamp_ms: Option<u64>,
+
+        #[serde(
+            rename = "explicitHashKey",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        pub explicit_hash_key: Option<String>,
+
+        #[serde(
+            rename = "encryptionType",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        pub encryption_type: Option<String>,
     }
 
     impl Record {
         pub fn to_bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
             BASE64_STANDARD.decode(&self.base64_data)
         }
+
+        pub fn extended_sequence_number(&self) -> ExtendedSequenceNumber {
+            ExtendedSequenceNumber::new(&self.sequence_number, self.sub_sequence_number)
+        }
+
+        pub fn arrival_time(&self) -> Option<SystemTime> {
+            self.approximate_arrival_timestamp_ms
+                .map(|ms| UNIX_EPOCH + Duration::from_millis(ms))
+        }
+
+        /// Time since the record arrived in the stream; zero if the clocks disagree.
+        pub fn age(&self) -> Option<Duration> {
+            self.arrival_time()
+                .map(|arrival| arrival.elapsed().unwrap_or_default())
+        }
+
+        /// 128-bit hash key Kinesis uses to route the record to a shard.
+        ///
+        /// This is the explicit hash key if one was set, otherwise the MD5
+        /// digest of the partition key read as a big-endian integer.
+        pub fn hash_key(&self) -> u128 {
+            self.explicit_hash_key
+                .as_deref()
+                .and_then(|key| key.parse().ok())
+                .unwrap_or_else(|| u128::from_be_bytes(Md5::digest(&self.partition_key).into()))
+        }
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct LeaseLostMessage {}
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ProcessRecordsMessage {
         #[serde(rename = "records")]
         pub records: Vec<Record>,
@@ -166,15 +219,15 @@ pub mod input {
         pub millis_behind_latest: Option<u64>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ShardEndedMessage {}
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ShutdownMessage {
         #[serde(rename = "reason", skip_serializing_if = "Option::is_none", default)]
         pub reason: Option<String>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ShutdownRequestedMessage {}
 }
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+use tokio::task::{JoinError, JoinSet};
+
+use crate::checkpoint::{CheckpointError, Checkpointer};
+use crate::message::input::{ProcessRecordsMessage, Record};
+use crate::transport::Transport;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParallelError<TransportError, HandlerError> {
+    #[error(transparent)]
+    CheckpointError(CheckpointError<TransportError>),
+
+    #[error("failed to process record: {sequence_number}")]
+    HandlerError {
+        sequence_number: String,
+        error: HandlerError,
+    },
+
+    #[error(transparent)]
+    TaskError(JoinError),
+}
+
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    /// Number of tasks processing records (or partition keys) at the same time.
+    pub concurrency: usize,
+
+    /// Processes records sharing a partition key one after another in batch order.
+    pub ordered_by_partition_key: bool,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            ordered_by_partition_key: false,
+        }
+    }
+}
+
+/// Processes the records of a batch concurrently on a bounded set of tokio tasks.
+///
+/// Once all records are done (or the first one failed) the checkpointer is
+/// advanced to the highest record for which it and all records before it
+/// completed successfully.
+pub async fn process_parallel<T, F, Fut, E>(
+    msg: ProcessRecordsMessage,
+    checkpointer: &mut Checkpointer<'_, T>,
+    options: &ParallelOptions,
+    handler: F,
+) -> Result<(), ParallelError<T::Error, E>>
+where
+    T: Transport,
+    F: Fn(Record) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Send + 'static,
+{
+    let sequence_numbers = msg
+        .records
+        .iter()
+        .map(|r| (r.sequence_number.clone(), r.sub_sequence_number))
+        .collect::<Vec<_>>();
+
+    let lanes = into_lanes(msg.records, options.ordered_by_partition_key);
+
+    let workers = options.concurrency.clamp(1, lanes.len().max(1));
+    let lanes = Arc::new(Mutex::new(lanes.into_iter()));
+    let handler = Arc::new(handler);
+    let stopped = Arc::new(AtomicBool::new(false));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..workers {
+        let lanes = Arc::clone(&lanes);
+        let handler = Arc::clone(&handler);
+        let stopped = Arc::clone(&stopped);
+        let tx = tx.clone();
+
+        tasks.spawn(async move {
+            loop {
+                let Some(lane) = lanes.lock().expect("Lane queue poisoned").next() else {
+                    return;
+                };
+
+                for (index, record) in lane {
+                    // Set once another record failed
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let result = handler(record).await;
+                    let failed = result.is_err();
+
+                    if failed {
+                        stopped.store(true, Ordering::Relaxed);
+                    }
+
+                    if tx.send((index, result)).is_err() || failed {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    drop(tx);
+
+    let mut completed = vec![false; sequence_numbers.len()];
+    let mut contiguous = 0;
+    let mut failure = None;
+
+    while let Some((index, result)) = rx.recv().await {
+        match result {
+            Ok(()) => {
+                completed[index] = true;
+                while contiguous < completed.len() && completed[contiguous] {
+                    contiguous += 1;
+                }
+            }
+            Err(error) => {
+                // Keep the failure closest to the start of the batch
+                if failure.as_ref().is_none_or(|(i, _)| index < *i) {
+                    failure = Some((index, error));
+                }
+            }
+        }
+    }
+
+    let mut task_error = None;
+    while let Some(result) = tasks.join_next().await {
+        if let Err(err) = result {
+            task_error.get_or_insert(err);
+        }
+    }
+
+    if let Some(index) = contiguous.checked_sub(1) {
+        let (sequence_number, sub_sequence_number) = sequence_numbers[index].clone();
+
+        checkpointer
+            .checkpoint(Some(sequence_number), sub_sequence_number)
+            .await
+            .map_err(ParallelError::CheckpointError)?;
+    }
+
+    if let Some((index, error)) = failure {
+        return Err(ParallelError::HandlerError {
+            sequence_number: sequence_numbers[index].0.clone(),
+            error,
+        });
+    }
+
+    if let Some(err) = task_error {
+        return Err(ParallelError::TaskError(err));
+    }
+
+    Ok(())
+}
+
+fn into_lanes(records: Vec<Record>, by_partition_key: bool) -> Vec<Vec<(usize, Record)>> {
+    if !by_partition_key {
+        return records
+            .into_iter()
+            .enumerate()
+            .map(|record| vec![record])
+            .collect();
+    }
+
+    let mut lanes: Vec<Vec<(usize, Record)>> = vec![];
+    let mut lane_by_key = HashMap::new();
+
+    for (index, record) in records.into_iter().enumerate() {
+        let lane = *lane_by_key
+            .entry(record.partition_key.clone())
+            .or_insert_with(|| {
+                lanes.push(vec![]);
+                lanes.len() - 1
+            });
+
+        lanes[lane].push((index, record));
+    }
+
+    lanes
+}
@@ -0,0 +1,329 @@
+use std::future::Future;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::checkpoint::{CheckpointError, CheckpointHandle, Checkpointer};
+use crate::context::ShardContext;
+use crate::message::input::{
+    InitializeMessage, LeaseLostMessage, ProcessRecordsMessage, ShardEndedMessage, ShutdownMessage,
+    ShutdownRequestedMessage,
+};
+use crate::processor::Processor;
+use crate::sequence::ExtendedSequenceNumber;
+use crate::transport::Transport;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError<TransportError, WorkerError> {
+    #[error(transparent)]
+    CheckpointError(CheckpointError<TransportError>),
+
+    #[error(transparent)]
+    WorkerError(WorkerError),
+
+    #[error(transparent)]
+    TaskError(JoinError),
+
+    #[error("pipeline already shut down")]
+    Closed,
+}
+
+/// Counterpart of [`Processor`] which runs on a background task of a [`Pipeline`].
+///
+/// The context is a snapshot taken when the action was queued.
+#[async_trait]
+pub trait Worker: Send + 'static {
+    type Error: Send + 'static;
+
+    async fn initialize(
+        &mut self,
+        ctx: &ShardContext,
+        msg: InitializeMessage,
+    ) -> Result<(), Self::Error>;
+
+    /// Checkpointing without sequence number covers `msg` and the batches
+    /// before it, not those queued after it.
+    async fn process_records(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ProcessRecordsMessage,
+        checkpointer: &CheckpointHandle,
+    ) -> Result<(), Self::Error>;
+
+    async fn shutdown(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownMessage,
+        checkpointer: &CheckpointHandle,
+    ) -> Result<(), Self::Error>;
+
+    async fn shutdown_requested(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownRequestedMessage,
+        checkpointer: &CheckpointHandle,
+    ) -> Result<(), Self::Error>;
+
+    async fn lease_lost(
+        &mut self,
+        ctx: &ShardContext,
+        msg: LeaseLostMessage,
+    ) -> Result<(), Self::Error>;
+
+    async fn shard_ended(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShardEndedMessage,
+    ) -> Result<(), Self::Error>;
+}
+
+enum Action {
+    Initialize(InitializeMessage),
+    ProcessRecords(ProcessRecordsMessage),
+    Shutdown(ShutdownMessage),
+    ShutdownRequested(ShutdownRequestedMessage),
+    LeaseLost(LeaseLostMessage),
+    ShardEnded(ShardEndedMessage),
+}
+
+struct Job {
+    ctx: ShardContext,
+    action: Action,
+
+    /// Last record queued with this job, which checkpoints without sequence
+    /// number refer to; the daemon may have delivered further batches since.
+    position: Option<ExtendedSequenceNumber>,
+    done: Option<oneshot::Sender<()>>,
+}
+
+/// [`Processor`] which acknowledges actions as soon as they are queued for a
+/// [`Worker`] running on a background task.
+///
+/// At most `capacity` actions are queued; once the worker falls behind,
+/// `processRecords` is only acknowledged after space frees up. Lifecycle
+/// actions (shutdown, lease lost, shard ended) wait for the worker to catch up.
+pub struct Pipeline<W: Worker> {
+    jobs: Option<mpsc::Sender<Job>>,
+    queued: Option<ExtendedSequenceNumber>,
+    task: Option<JoinHandle<Result<(), W::Error>>>,
+}
+
+impl<W: Worker> Pipeline<W> {
+    /// Spawns the worker onto the current tokio runtime.
+    pub fn new(worker: W, capacity: usize) -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::channel(capacity.max(1));
+
+        Self {
+            jobs: Some(jobs_tx),
+            queued: None,
+            task: Some(tokio::spawn(work(worker, jobs_rx))),
+        }
+    }
+
+    async fn submit<T: Transport>(
+        &mut self,
+        ctx: &ShardContext,
+        action: Action,
+        done: Option<oneshot::Sender<()>>,
+        checkpointer: Option<&mut Checkpointer<'_, T>>,
+    ) -> Result<(), PipelineError<T::Error, W::Error>> {
+        let jobs = self.jobs.clone().ok_or(PipelineError::Closed)?;
+
+        // Lifecycle actions wait for the worker, so everything delivered is processed
+        let position = match &action {
+            Action::ProcessRecords(msg) => {
+                if let Some(record) = msg.records.last() {
+                    self.queued = Some(record.extended_sequence_number());
+                }
+
+                self.queued.clone()
+            }
+            _ => None,
+        };
+
+        let permit = serve_until(checkpointer, jobs.reserve())
+            .await
+            .map_err(PipelineError::CheckpointError)?;
+
+        match permit {
+            Ok(permit) => {
+                permit.send(Job {
+                    ctx: ctx.clone(),
+                    action,
+                    position,
+                    done,
+                });
+                Ok(())
+            }
+            // Worker stopped; surface its result
+            Err(_) => Err(self
+                .join::<T>(None)
+                .await
+                .err()
+                .unwrap_or(PipelineError::Closed)),
+        }
+    }
+
+    async fn submit_and_wait<T: Transport>(
+        &mut self,
+        ctx: &ShardContext,
+        action: Action,
+        mut checkpointer: Option<&mut Checkpointer<'_, T>>,
+    ) -> Result<(), PipelineError<T::Error, W::Error>> {
+        let (done_tx, done_rx) = oneshot::channel();
+
+        self.submit(ctx, action, Some(done_tx), checkpointer.as_deref_mut())
+            .await?;
+
+        let done = serve_until(checkpointer.as_deref_mut(), done_rx)
+            .await
+            .map_err(PipelineError::CheckpointError)?;
+
+        match done {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self
+                .join(checkpointer)
+                .await
+                .err()
+                .unwrap_or(PipelineError::Closed)),
+        }
+    }
+
+    async fn join<T: Transport>(
+        &mut self,
+        checkpointer: Option<&mut Checkpointer<'_, T>>,
+    ) -> Result<(), PipelineError<T::Error, W::Error>> {
+        self.jobs = None;
+
+        let Some(task) = self.task.take() else {
+            return Ok(());
+        };
+
+        serve_until(checkpointer, task)
+            .await
+            .map_err(PipelineError::CheckpointError)?
+            .map_err(PipelineError::TaskError)?
+            .map_err(PipelineError::WorkerError)
+    }
+}
+
+#[async_trait]
+impl<T, W> Processor<T> for Pipeline<W>
+where
+    T: Transport + Send,
+    T::Error: Send,
+    W: Worker,
+{
+    type Error = PipelineError<T::Error, W::Error>;
+
+    async fn initialize(
+        &mut self,
+        ctx: &ShardContext,
+        msg: InitializeMessage,
+    ) -> Result<(), Self::Error> {
+        self.submit::<T>(ctx, Action::Initialize(msg), None, None)
+            .await
+    }
+
+    async fn process_records(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ProcessRecordsMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        self.submit(ctx, Action::ProcessRecords(msg), None, Some(checkpointer))
+            .await
+    }
+
+    async fn shutdown(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        self.submit_and_wait(ctx, Action::Shutdown(msg), Some(&mut *checkpointer))
+            .await?;
+        self.join(Some(checkpointer)).await
+    }
+
+    async fn shutdown_requested(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShutdownRequestedMessage,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), Self::Error> {
+        self.submit_and_wait(ctx, Action::ShutdownRequested(msg), Some(checkpointer))
+            .await
+    }
+
+    async fn lease_lost(
+        &mut self,
+        ctx: &ShardContext,
+        msg: LeaseLostMessage,
+    ) -> Result<(), Self::Error> {
+        self.submit_and_wait::<T>(ctx, Action::LeaseLost(msg), None)
+            .await?;
+        self.join::<T>(None).await
+    }
+
+    async fn shard_ended(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShardEndedMessage,
+    ) -> Result<(), Self::Error> {
+        self.submit_and_wait::<T>(ctx, Action::ShardEnded(msg), None)
+            .await?;
+        self.join::<T>(None).await
+    }
+}
+
+async fn work<W: Worker>(mut worker: W, mut jobs: mpsc::Receiver<Job>) -> Result<(), W::Error> {
+    while let Some(Job {
+        mut ctx,
+        action,
+        position,
+        done,
+    }) = jobs.recv().await
+    {
+        if let Some(position) = position {
+            ctx.checkpoint_handle = ctx.checkpoint_handle.up_to(position);
+        }
+
+        let checkpointer = ctx.checkpoint_handle();
+
+        match action {
+            Action::Initialize(msg) => worker.initialize(&ctx, msg).await?,
+            Action::ProcessRecords(msg) => worker.process_records(&ctx, msg, &checkpointer).await?,
+            Action::Shutdown(msg) => worker.shutdown(&ctx, msg, &checkpointer).await?,
+            Action::ShutdownRequested(msg) => {
+                worker.shutdown_requested(&ctx, msg, &checkpointer).await?
+            }
+            Action::LeaseLost(msg) => worker.lease_lost(&ctx, msg).await?,
+            Action::ShardEnded(msg) => worker.shard_ended(&ctx, msg).await?,
+        }
+
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+    }
+
+    Ok(())
+}
+
+/// Drives `fut` to completion, performing checkpoints requested by the worker
+/// in the meantime if the current action accepts them.
+async fn serve_until<T, F>(
+    checkpointer: Option<&mut Checkpointer<'_, T>>,
+    fut: F,
+) -> Result<F::Output, CheckpointError<T::Error>>
+where
+    T: Transport,
+    F: Future,
+{
+    match checkpointer {
+        Some(checkpointer) => checkpointer.serve_until(fut).await,
+        // Requests are answered by `run` alongside the current action
+        None => Ok(fut.await),
+    }
+}
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 
 use crate::{
+    context::ShardContext,
     message::input::{
         InitializeMessage, LeaseLostMessage, ProcessRecordsMessage, ShardEndedMessage,
         ShutdownMessage, ShutdownRequestedMessage,
@@ -17,27 +18,42 @@ where
 {
     type Error;
 
-    async fn initialize(&mut self, msg: InitializeMessage) -> Result<(), Self::Error>;
+    async fn initialize(
+        &mut self,
+        ctx: &ShardContext,
+        msg: InitializeMessage,
+    ) -> Result<(), Self::Error>;
 
     async fn process_records(
         &mut self,
+        ctx: &ShardContext,
         msg: ProcessRecordsMessage,
         checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error>;
 
     async fn shutdown(
         &mut self,
+        ctx: &ShardContext,
         msg: ShutdownMessage,
         checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error>;
 
     async fn shutdown_requested(
         &mut self,
+        ctx: &ShardContext,
         msg: ShutdownRequestedMessage,
         checkpointer: &mut Checkpointer<'_, T>,
     ) -> Result<(), Self::Error>;
 
-    async fn lease_lost(&mut self, msg: LeaseLostMessage) -> Result<(), Self::Error>;
+    async fn lease_lost(
+        &mut self,
+        ctx: &ShardContext,
+        msg: LeaseLostMessage,
+    ) -> Result<(), Self::Error>;
 
-    async fn shard_ended(&mut self, msg: ShardEndedMessage) -> Result<(), Self::Error>;
+    async fn shard_ended(
+        &mut self,
+        ctx: &ShardContext,
+        msg: ShardEndedMessage,
+    ) -> Result<(), Self::Error>;
 }
@@ -0,0 +1,170 @@
+use crate::message::input::Message as MessageIn;
+use crate::message::input::{LeaseLostMessage, ShardEndedMessage};
+use crate::sequence::ExtendedSequenceNumber;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProtocolViolation {
+    #[error("received {action} before initialize")]
+    NotInitialized { action: &'static str },
+
+    #[error("received initialize more than once")]
+    AlreadyInitialized,
+
+    #[error("received {action} after the shard was shut down")]
+    AfterEnd { action: &'static str },
+
+    #[error("received empty shard id")]
+    EmptyShardId,
+
+    #[error("received invalid sequence number: {sequence_number}")]
+    InvalidSequenceNumber { sequence_number: String },
+
+    #[error("received sequence number {current} after {previous}")]
+    OutOfOrder {
+        previous: ExtendedSequenceNumber,
+        current: ExtendedSequenceNumber,
+    },
+}
+
+/// How [`run`](crate::run_with_options) reacts to a [`ProtocolViolation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Fails with [`RunError::ProtocolViolation`](crate::RunError::ProtocolViolation).
+    Strict,
+
+    /// Reports the violation through [`Transport::write_error`](crate::transport::Transport::write_error)
+    /// and continues.
+    #[default]
+    Warn,
+
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Uninitialized,
+    Processing,
+    Ended,
+}
+
+/// Tracks the expected order `initialize → processRecords* → shutdown | leaseLost | shardEnded`.
+#[derive(Debug)]
+pub(crate) struct ProtocolState {
+    phase: Phase,
+    last: Option<ExtendedSequenceNumber>,
+}
+
+impl ProtocolState {
+    pub(crate) fn new() -> Self {
+        Self {
+            phase: Phase::Uninitialized,
+            last: None,
+        }
+    }
+
+    pub(crate) fn validate(&mut self, msg: &MessageIn) -> Vec<ProtocolViolation> {
+        let mut violations = vec![];
+        let action = msg.id();
+
+        match msg {
+            MessageIn::Initialize(m) => {
+                if self.phase != Phase::Uninitialized {
+                    violations.push(ProtocolViolation::AlreadyInitialized);
+                }
+
+                if m.shard_id.is_empty() {
+                    violations.push(ProtocolViolation::EmptyShardId);
+                }
+
+                if let Some(seq) = m.extended_sequence_number()
+                    && !seq.is_sentinel()
+                    && !seq.is_numeric()
+                {
+                    violations.push(ProtocolViolation::InvalidSequenceNumber {
+                        sequence_number: seq.sequence_number,
+                    });
+                }
+
+                self.phase = Phase::Processing;
+            }
+            MessageIn::ProcessRecords(m) => {
+                violations.extend(self.expect_processing(action));
+
+                for record in &m.records {
+                    let current = record.extended_sequence_number();
+
+                    if !current.is_numeric() {
+                        violations.push(ProtocolViolation::InvalidSequenceNumber {
+                            sequence_number: current.sequence_number,
+                        });
+                        continue;
+                    }
+
+                    if let Some(previous) = self.last.take_if(|previous| *previous >= current) {
+                        violations.push(ProtocolViolation::OutOfOrder {
+                            previous,
+                            current: current.clone(),
+                        });
+                    }
+
+                    self.last = Some(current);
+                }
+            }
+            MessageIn::ShutdownRequested(_) => {
+                violations.extend(self.expect_processing(action));
+            }
+            MessageIn::Shutdown(_) | MessageIn::LeaseLost(_) | MessageIn::ShardEnded(_) => {
+                violations.extend(self.expect_processing(action));
+                self.phase = Phase::Ended;
+            }
+            // Unsolicited checkpoint responses are rejected by `run` itself
+            MessageIn::Checkpoint(_) => {}
+        }
+
+        violations
+    }
+
+    fn expect_processing(&self, action: &'static str) -> Option<ProtocolViolation> {
+        match self.phase {
+            Phase::Uninitialized => Some(ProtocolViolation::NotInitialized { action }),
+            Phase::Processing => None,
+            Phase::Ended => Some(ProtocolViolation::AfterEnd { action }),
+        }
+    }
+}
+
+/// MultiLang protocol generation spoken by the daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// Treats `shutdown` actions carrying a KCL 1.x reason as [`ProtocolVersion::V1`]
+    /// and everything else as [`ProtocolVersion::V2`].
+    #[default]
+    Auto,
+
+    /// KCL 1.x, which reports the end of a lease as `shutdown` with a reason.
+    V1,
+
+    /// KCL 2.x and later, which use `leaseLost` and `shardEnded`.
+    V2,
+}
+
+impl ProtocolVersion {
+    /// Maps KCL 1.x shutdown reasons onto their KCL 2.x actions.
+    ///
+    /// `TERMINATE` becomes `shardEnded` and `ZOMBIE` becomes `leaseLost`.
+    /// `REQUESTED` and unknown reasons are left as `shutdown`.
+    pub(crate) fn translate(self, msg: MessageIn) -> MessageIn {
+        if self == ProtocolVersion::V2 {
+            return msg;
+        }
+
+        match msg {
+            MessageIn::Shutdown(m) => match m.reason.as_deref() {
+                Some("TERMINATE") => MessageIn::ShardEnded(ShardEndedMessage {}),
+                Some("ZOMBIE") => MessageIn::LeaseLost(LeaseLostMessage {}),
+                _ => MessageIn::Shutdown(m),
+            },
+            msg => msg,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::json;
+use crate::message::input::{CheckpointMessage, Message as MessageIn};
+use crate::message::output::Message as MessageOut;
+use crate::processor::Processor;
+use crate::transport::Transport;
+use crate::{RunError, RunOptions};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecordingError<TransportError> {
+    #[error(transparent)]
+    TransportError(TransportError),
+
+    #[error(transparent)]
+    IoError(std::io::Error),
+
+    #[error(transparent)]
+    SerializationError(json::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("malformed entry in line {line} of the recording")]
+    MalformedEntry { line: usize },
+
+    #[error("reached the end of the recording")]
+    EndOfRecording,
+
+    #[error(transparent)]
+    IoError(std::io::Error),
+
+    #[error(transparent)]
+    SerializationError(json::Error),
+}
+
+/// One line of a recording.
+///
+/// Lines are prefixed with `<` for messages read from the daemon, `>` for
+/// messages written to it and `!` for errors, followed by a space and JSON.
+#[derive(Debug)]
+pub enum Entry {
+    Inbound(MessageIn),
+    Outbound(MessageOut),
+    Error(String),
+}
+
+impl Entry {
+    pub fn to_line(&self) -> Result<String, json::Error> {
+        match self {
+            Entry::Inbound(msg) => line('<', msg),
+            Entry::Outbound(msg) => line('>', msg),
+            Entry::Error(error) => line('!', error),
+        }
+    }
+
+    pub fn from_line(line: &str) -> Option<Result<Self, json::Error>> {
+        let (prefix, json) = line.split_once(' ')?;
+        let mut json = json.as_bytes().to_vec();
+
+        Some(match prefix {
+            "<" => json::from_slice(&mut json).map(Entry::Inbound),
+            ">" => json::from_slice(&mut json).map(Entry::Outbound),
+            "!" => json::from_slice(&mut json).map(Entry::Error),
+            _ => return None,
+        })
+    }
+
+    fn is_outbound(&self) -> bool {
+        !matches!(self, Entry::Inbound(_))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Recording {
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    /// Reads a recording written by [`RecordingTransport`], skipping empty lines.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(ReplayError::IoError)?;
+
+        let mut entries = vec![];
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = Entry::from_line(line)
+                .ok_or(ReplayError::MalformedEntry { line: index + 1 })?
+                .map_err(ReplayError::SerializationError)?;
+
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+/// Wraps a transport and appends every message and error passing through it
+/// to a file.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    file: BufWriter<tokio::fs::File>,
+}
+
+impl<T: Transport + Send> RecordingTransport<T> {
+    /// Appends to the file at `path` if it already exists.
+    pub async fn create(inner: T, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+
+        Ok(Self {
+            inner,
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    async fn record(
+        &mut self,
+        prefix: char,
+        value: &(impl Serialize + Sync),
+    ) -> Result<(), RecordingError<T::Error>> {
+        let line = line(prefix, value).map_err(RecordingError::SerializationError)?;
+
+        self.file
+            .write_all(line.as_bytes())
+            .await
+            .map_err(RecordingError::IoError)?;
+        self.file
+            .write_u8(b'\n')
+            .await
+            .map_err(RecordingError::IoError)?;
+
+        // Flush every line so the recording survives a crash
+        self.file.flush().await.map_err(RecordingError::IoError)
+    }
+}
+
+#[async_trait]
+impl<T> Transport for RecordingTransport<T>
+where
+    T: Transport + Send,
+    T::Error: Send,
+{
+    type Error = RecordingError<T::Error>;
+
+    async fn write_error(&mut self, error: &str) -> Result<(), Self::Error> {
+        self.record('!', &error).await?;
+
+        self.inner
+            .write_error(error)
+            .await
+            .map_err(RecordingError::TransportError)
+    }
+
+    async fn write_message(&mut self, message: &MessageOut) -> Result<(), Self::Error> {
+        self.record('>', message).await?;
+
+        self.inner
+            .write_message(message)
+            .await
+            .map_err(RecordingError::TransportError)
+    }
+
+    async fn read_message(&mut self) -> Result<MessageIn, Self::Error> {
+        let msg = self
+            .inner
+            .read_message()
+            .await
+            .map_err(RecordingError::TransportError)?;
+
+        self.record('<', &msg).await?;
+
+        Ok(msg)
+    }
+}
+
+/// Feeds the inbound messages of a recording to [`run`](crate::run).
+///
+/// Recorded checkpoint replies are only delivered after the processor
+/// requested a checkpoint; if the recording has none at that point a
+/// successful reply is made up. Replies to checkpoints the processor no
+/// longer requests are skipped. Reading past the last message fails with
+/// [`ReplayError::EndOfRecording`].
+#[derive(Debug)]
+pub struct ReplayTransport {
+    inbound: VecDeque<MessageIn>,
+    pending_checkpoints: VecDeque<CheckpointMessage>,
+    output: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(recording: Recording) -> Self {
+        let inbound = recording
+            .entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Inbound(msg) => Some(msg),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            inbound,
+            pending_checkpoints: VecDeque::new(),
+            output: Arc::default(),
+        }
+    }
+
+    /// Messages and errors written so far.
+    pub fn output(&self) -> Arc<Mutex<Vec<Entry>>> {
+        self.output.clone()
+    }
+
+    fn push_output(&self, entry: Entry) {
+        self.output
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(entry);
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    type Error = ReplayError;
+
+    async fn write_error(&mut self, error: &str) -> Result<(), Self::Error> {
+        self.push_output(Entry::Error(error.to_owned()));
+
+        Ok(())
+    }
+
+    async fn write_message(&mut self, message: &MessageOut) -> Result<(), Self::Error> {
+        if let MessageOut::Checkpoint(m) = message {
+            self.pending_checkpoints.push_back(CheckpointMessage {
+                sequence_number: m.sequence_number.clone(),
+                sub_sequence_number: m.sub_sequence_number,
+                error: None,
+            });
+        }
+
+        self.push_output(Entry::Outbound(message.clone()));
+
+        Ok(())
+    }
+
+    async fn read_message(&mut self) -> Result<MessageIn, Self::Error> {
+        if let Some(requested) = self.pending_checkpoints.pop_front() {
+            if let Some(MessageIn::Checkpoint(_)) = self.inbound.front() {
+                return self.inbound.pop_front().ok_or(ReplayError::EndOfRecording);
+            }
+
+            return Ok(MessageIn::Checkpoint(requested));
+        }
+
+        while let Some(MessageIn::Checkpoint(_)) = self.inbound.front() {
+            self.inbound.pop_front();
+        }
+
+        self.inbound.pop_front().ok_or(ReplayError::EndOfRecording)
+    }
+}
+
+/// Outbound line which differs between the recording and the replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub index: usize,
+
+    /// Line of the recording; `None` if the replay wrote more lines.
+    pub expected: Option<String>,
+
+    /// Line of the replay; `None` if the replay wrote fewer lines.
+    pub actual: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ReplayReport {
+    pub output: Vec<Entry>,
+    pub differences: Vec<Difference>,
+}
+
+impl ReplayReport {
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Runs `processor` against a recording and compares the messages and
+/// errors it writes with the recorded ones, line by line.
+pub async fn replay<P: Processor<ReplayTransport>>(
+    recording: Recording,
+    processor: P,
+    options: RunOptions,
+) -> Result<ReplayReport, RunError<ReplayError, P::Error>> {
+    let expected = lines(&recording.entries).map_err(RunError::IoError)?;
+
+    let transport = ReplayTransport::new(recording);
+    let output = transport.output();
+
+    match crate::run_with_options(transport, processor, options).await {
+        Ok(()) | Err(RunError::IoError(ReplayError::EndOfRecording)) => {}
+        Err(err) => return Err(err),
+    }
+
+    let output = std::mem::take(&mut *output.lock().unwrap_or_else(|err| err.into_inner()));
+    let actual = lines(&output).map_err(RunError::IoError)?;
+
+    let differences = (0..expected.len().max(actual.len()))
+        .filter_map(|index| {
+            let expected = expected.get(index);
+            let actual = actual.get(index);
+
+            (expected != actual).then(|| Difference {
+                index,
+                expected: expected.cloned(),
+                actual: actual.cloned(),
+            })
+        })
+        .collect();
+
+    Ok(ReplayReport {
+        output,
+        differences,
+    })
+}
+
+fn lines(entries: &[Entry]) -> Result<Vec<String>, ReplayError> {
+    entries
+        .iter()
+        .filter(|entry| entry.is_outbound())
+        .map(|entry| entry.to_line().map_err(ReplayError::SerializationError))
+        .collect()
+}
+
+fn line(prefix: char, value: &impl Serialize) -> Result<String, json::Error> {
+    Ok(format!("{prefix} {}", json::to_string(value)?))
+}
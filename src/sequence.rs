@@ -0,0 +1,214 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+pub const TRIM_HORIZON: &str = "TRIM_HORIZON";
+pub const LATEST: &str = "LATEST";
+pub const AT_TIMESTAMP: &str = "AT_TIMESTAMP";
+pub const SHARD_END: &str = "SHARD_END";
+
+/// Sequence number of a (possibly aggregated) record.
+///
+/// Orders numerically like the KCL does, with the sentinel values
+/// `AT_TIMESTAMP`, `TRIM_HORIZON` and `LATEST` before and `SHARD_END` after
+/// every real sequence number. Leading zeros and a missing sub-sequence
+/// number (which is `0`) are ignored, also for equality and hashing.
+#[derive(Debug, Clone)]
+pub struct ExtendedSequenceNumber {
+    pub sequence_number: String,
+    pub sub_sequence_number: Option<u64>,
+}
+
+impl ExtendedSequenceNumber {
+    pub fn new(sequence_number: impl Into<String>, sub_sequence_number: Option<u64>) -> Self {
+        Self {
+            sequence_number: sequence_number.into(),
+            sub_sequence_number,
+        }
+    }
+
+    pub fn is_sentinel(&self) -> bool {
+        matches!(
+            self.sequence_number.as_str(),
+            TRIM_HORIZON | LATEST | AT_TIMESTAMP | SHARD_END
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        !self.sequence_number.is_empty() && self.sequence_number.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    fn rank(&self) -> i8 {
+        match self.sequence_number.as_str() {
+            AT_TIMESTAMP => -3,
+            TRIM_HORIZON => -2,
+            LATEST => -1,
+            SHARD_END => 1,
+            _ => 0,
+        }
+    }
+
+    fn digits(&self) -> &str {
+        self.sequence_number.trim_start_matches('0')
+    }
+}
+
+impl Ord for ExtendedSequenceNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank()).then_with(|| {
+            let a = self.digits();
+            let b = other.digits();
+
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b)).then_with(|| {
+                self.sub_sequence_number
+                    .unwrap_or(0)
+                    .cmp(&other.sub_sequence_number.unwrap_or(0))
+            })
+        })
+    }
+}
+
+impl PartialEq for ExtendedSequenceNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ExtendedSequenceNumber {}
+
+impl Hash for ExtendedSequenceNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digits().hash(state);
+        self.sub_sequence_number.unwrap_or(0).hash(state);
+    }
+}
+
+impl PartialOrd for ExtendedSequenceNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ExtendedSequenceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sub_sequence_number {
+            Some(sub) => write!(f, "{}:{sub}", self.sequence_number),
+            None => f.write_str(&self.sequence_number),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid sequence number: {0}")]
+pub struct ParseSequenceNumberError(String);
+
+impl FromStr for ExtendedSequenceNumber {
+    type Err = ParseSequenceNumberError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (sequence_number, sub_sequence_number) = match s.split_once(':') {
+            Some((seq, sub)) => (
+                seq,
+                Some(
+                    sub.parse()
+                        .map_err(|_| ParseSequenceNumberError(s.to_owned()))?,
+                ),
+            ),
+            None => (s, None),
+        };
+
+        if sequence_number.is_empty() {
+            return Err(ParseSequenceNumberError(s.to_owned()));
+        }
+
+        Ok(Self::new(sequence_number, sub_sequence_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::hash::BuildHasher as _;
+
+    use super::*;
+
+    fn seq(sequence_number: &str, sub_sequence_number: Option<u64>) -> ExtendedSequenceNumber {
+        ExtendedSequenceNumber::new(sequence_number, sub_sequence_number)
+    }
+
+    #[test]
+    fn sentinels_order_around_numbers() {
+        let ordered = [
+            seq(AT_TIMESTAMP, None),
+            seq(TRIM_HORIZON, None),
+            seq(LATEST, None),
+            seq("0", None),
+            seq(
+                "49590338271490256608559692538361571095921575989136588898",
+                None,
+            ),
+            seq(SHARD_END, None),
+        ];
+
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(a.cmp(b), i.cmp(&j), "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn compares_numerically() {
+        assert!(seq("9", None) < seq("10", None));
+        assert!(seq("100", None) > seq("99", None));
+        assert!(seq("123", None) < seq("124", None));
+    }
+
+    #[test]
+    fn ignores_leading_zeros() {
+        assert_eq!(seq("0042", None), seq("42", None));
+        assert_eq!(seq("000", None), seq("0", None));
+        assert!(seq("0043", None) > seq("42", None));
+        assert!(seq("009", None) < seq("10", None));
+    }
+
+    #[test]
+    fn orders_by_sub_sequence_number() {
+        assert!(seq("5", Some(1)) < seq("5", Some(2)));
+        assert!(seq("5", Some(9)) < seq("6", Some(0)));
+        assert_eq!(seq("5", None), seq("5", Some(0)));
+        assert!(seq("5", None) < seq("5", Some(1)));
+    }
+
+    #[test]
+    fn hash_agrees_with_eq() {
+        let state = std::hash::RandomState::new();
+        let equal = [
+            (seq("42", None), seq("0042", Some(0))),
+            (seq("0", None), seq("000", None)),
+            (seq(SHARD_END, None), seq(SHARD_END, Some(0))),
+        ];
+
+        for (a, b) in &equal {
+            assert_eq!(a, b);
+            assert_eq!(state.hash_one(a), state.hash_one(b), "{a} vs {b}");
+        }
+
+        let set = [seq("7", None), seq("007", Some(0)), seq("7", Some(1))]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn parses_sub_sequence_number() {
+        let parsed = "0042:3".parse::<ExtendedSequenceNumber>().unwrap();
+        assert_eq!(parsed, seq("42", Some(3)));
+        assert_eq!(parsed.to_string(), "0042:3");
+
+        assert!("".parse::<ExtendedSequenceNumber>().is_err());
+        assert!("42:x".parse::<ExtendedSequenceNumber>().is_err());
+    }
+}
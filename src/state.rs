@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::checkpoint::CheckpointHook;
+use crate::json;
+use crate::message::input::InitializeMessage;
+use crate::sequence::ExtendedSequenceNumber;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateError<BackendError> {
+    #[error(transparent)]
+    BackendError(BackendError),
+
+    #[error(transparent)]
+    SerializationError(json::Error),
+
+    #[error("state used before restore")]
+    Uninitialized,
+}
+
+/// Storage for serialized state snapshots, keyed by shard and the sequence
+/// number they were taken at.
+#[async_trait]
+pub trait StateBackend: Send {
+    type Error;
+
+    async fn load(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    async fn save(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+        data: &[u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Removes all snapshots of the shard except the given one.
+    async fn retain(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Stores snapshots as files in one directory per shard.
+#[derive(Debug, Clone)]
+pub struct FileStateBackend {
+    directory: PathBuf,
+}
+
+impl FileStateBackend {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn file_name(sequence_number: &ExtendedSequenceNumber) -> String {
+        format!(
+            "{}_{}.state",
+            sequence_number.sequence_number,
+            sequence_number.sub_sequence_number.unwrap_or(0)
+        )
+    }
+}
+
+#[async_trait]
+impl StateBackend for FileStateBackend {
+    type Error = std::io::Error;
+
+    async fn load(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let path = self
+            .directory
+            .join(shard_id)
+            .join(Self::file_name(sequence_number));
+
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn save(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        let directory = self.directory.join(shard_id);
+        tokio::fs::create_dir_all(&directory).await?;
+
+        crate::fs::write_atomic(&directory.join(Self::file_name(sequence_number)), data).await
+    }
+
+    async fn retain(
+        &mut self,
+        shard_id: &str,
+        sequence_number: &ExtendedSequenceNumber,
+    ) -> Result<(), Self::Error> {
+        let keep = Self::file_name(sequence_number);
+        let mut entries = tokio::fs::read_dir(self.directory.join(shard_id)).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name() != keep.as_str() {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// State of a processor which follows the checkpoints of its shard.
+///
+/// Attached to a checkpointer with
+/// [`Checkpointer::with_hook`](crate::checkpoint::Checkpointer::with_hook),
+/// every checkpoint writes a snapshot first and only drops older snapshots
+/// once the daemon confirmed it. On [`ShardState::restore`] the snapshot taken
+/// at the sequence number the daemon resumes from is loaded, so state and
+/// processed records always match.
+#[derive(Debug)]
+pub struct ShardState<S, B> {
+    backend: B,
+    shard_id: Option<String>,
+    state: S,
+}
+
+impl<S, B> ShardState<S, B>
+where
+    S: Serialize + DeserializeOwned + Default,
+    B: StateBackend,
+{
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            shard_id: None,
+            state: S::default(),
+        }
+    }
+
+    /// Loads the snapshot matching the starting position of the shard.
+    ///
+    /// Returns `false` if no matching snapshot exists and the state was reset
+    /// to its default.
+    pub async fn restore(&mut self, msg: &InitializeMessage) -> Result<bool, StateError<B::Error>> {
+        self.shard_id = Some(msg.shard_id.clone());
+        self.state = S::default();
+
+        let Some(sequence_number) = msg
+            .extended_sequence_number()
+            .filter(|seq| !seq.is_sentinel())
+        else {
+            return Ok(false);
+        };
+
+        let Some(mut data) = self
+            .backend
+            .load(&msg.shard_id, &sequence_number)
+            .await
+            .map_err(StateError::BackendError)?
+        else {
+            return Ok(false);
+        };
+
+        self.state = json::from_slice(&mut data).map_err(StateError::SerializationError)?;
+
+        Ok(true)
+    }
+
+    pub fn get(&self) -> &S {
+        &self.state
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    fn shard_id(&self) -> Result<&str, StateError<B::Error>> {
+        self.shard_id.as_deref().ok_or(StateError::Uninitialized)
+    }
+}
+
+#[async_trait]
+impl<S, B> CheckpointHook for ShardState<S, B>
+where
+    S: Serialize + DeserializeOwned + Default + Send,
+    B: StateBackend,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn before_checkpoint(
+        &mut self,
+        position: &ExtendedSequenceNumber,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let data = json::to_vec(&self.state).map_err(StateError::<B::Error>::SerializationError)?;
+        let shard_id = self.shard_id()?.to_owned();
+
+        self.backend
+            .save(&shard_id, position, &data)
+            .await
+            .map_err(StateError::BackendError)?;
+
+        Ok(())
+    }
+
+    async fn after_checkpoint(
+        &mut self,
+        position: &ExtendedSequenceNumber,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let shard_id = self.shard_id()?.to_owned();
+
+        self.backend
+            .retain(&shard_id, position)
+            .await
+            .map_err(StateError::BackendError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::message::input::Message as MessageIn;
+use crate::message::output::Message as MessageOut;
+use crate::transport::Transport;
+use crate::worker::WorkerProcess;
+
+/// Set for worker processes started by a [`Supervisor`].
+pub const SUPERVISED_ENV: &str = "KCL_ASYNC_SUPERVISED";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorError<TransportError> {
+    #[error(transparent)]
+    TransportError(TransportError),
+
+    #[error("worker crashed {restarts} times in a row")]
+    RestartLimit { restarts: u32 },
+
+    #[error("expected checkpoint reply from daemon")]
+    UnexpectedMessage(MessageIn),
+
+    #[error(transparent)]
+    IoError(std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct SupervisorOptions {
+    /// Restarts allowed without the worker acknowledging an action in between.
+    pub max_restarts: u32,
+
+    pub restart_delay: Duration,
+}
+
+impl Default for SupervisorOptions {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            restart_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Whether this process was started by a [`Supervisor`].
+pub fn is_supervised() -> bool {
+    std::env::var_os(SUPERVISED_ENV).is_some()
+}
+
+/// Runs the worker as a child process and restarts it when it crashes.
+///
+/// Messages are proxied between the daemon and the child. The supervisor
+/// stays alive while the child restarts, so the daemon keeps the lease. A
+/// restarted child first receives the original `initialize` and then the
+/// action which was not acknowledged yet; records may therefore be processed
+/// twice.
+///
+/// A binary can supervise itself by calling [`run`](crate::run) if
+/// [`is_supervised`] and [`Supervisor::current_exe`] otherwise.
+#[derive(Debug)]
+pub struct Supervisor<T> {
+    transport: T,
+    command: Command,
+    options: SupervisorOptions,
+    process: Option<WorkerProcess>,
+    initialize: Option<MessageIn>,
+    restarts: u32,
+}
+
+impl<T> Supervisor<T>
+where
+    T: Transport + Send,
+{
+    pub fn new(transport: T, mut command: Command, options: SupervisorOptions) -> Self {
+        command.env(SUPERVISED_ENV, "1");
+
+        Self {
+            transport,
+            command,
+            options,
+            process: None,
+            initialize: None,
+            restarts: 0,
+        }
+    }
+
+    /// Supervises the running executable, started with the same arguments.
+    pub fn current_exe(transport: T, options: SupervisorOptions) -> std::io::Result<Self> {
+        let mut command = Command::new(std::env::current_exe()?);
+        command.args(std::env::args_os().skip(1));
+
+        Ok(Self::new(transport, command, options))
+    }
+
+    /// Proxies messages until reading from the daemon fails.
+    pub async fn run(mut self) -> Result<(), SupervisorError<T::Error>> {
+        loop {
+            let msg = self
+                .transport
+                .read_message()
+                .await
+                .map_err(SupervisorError::TransportError)?;
+
+            self.deliver(msg).await?;
+        }
+    }
+
+    async fn deliver(&mut self, msg: MessageIn) -> Result<(), SupervisorError<T::Error>> {
+        loop {
+            if self.process.is_none() && !self.restart(&msg).await? {
+                self.crashed().await?;
+                continue;
+            }
+
+            if !self.forward(&msg, true).await? {
+                self.crashed().await?;
+                continue;
+            }
+
+            self.restarts = 0;
+
+            if let MessageIn::Initialize(_) = msg {
+                self.initialize = Some(msg);
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Starts a new child and replays `initialize` to it, unless `msg` is the
+    /// `initialize` itself. Returns `false` if the child crashed again.
+    async fn restart(&mut self, msg: &MessageIn) -> Result<bool, SupervisorError<T::Error>> {
+        self.process =
+            Some(WorkerProcess::spawn(&mut self.command).map_err(SupervisorError::IoError)?);
+
+        match self.initialize.clone() {
+            Some(initialize) if !matches!(msg, MessageIn::Initialize(_)) => {
+                self.forward(&initialize, false).await
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// Sends `msg` to the child and proxies checkpoints until its status
+    /// arrives. Returns `false` if the child crashed before, including when
+    /// its output could not be read or was not the expected status; only
+    /// errors of the daemon side are returned.
+    async fn forward(
+        &mut self,
+        msg: &MessageIn,
+        forward_status: bool,
+    ) -> Result<bool, SupervisorError<T::Error>> {
+        let Some(process) = self.process.as_mut() else {
+            return Ok(false);
+        };
+
+        if process.write(msg).await.is_err() {
+            return Ok(false);
+        }
+
+        loop {
+            let out = match process.read().await {
+                Ok(Some(out)) => out,
+                Ok(None) => return Ok(false),
+                Err(err) => {
+                    self.transport
+                        .write_error(&format!("worker sent invalid output ({err})"))
+                        .await
+                        .map_err(SupervisorError::TransportError)?;
+
+                    return Ok(false);
+                }
+            };
+
+            match &out {
+                MessageOut::Status(status) if status.response_for == msg.id() => {
+                    if forward_status {
+                        self.transport
+                            .write_message(&out)
+                            .await
+                            .map_err(SupervisorError::TransportError)?;
+                    }
+
+                    return Ok(true);
+                }
+                MessageOut::Status(status) => {
+                    self.transport
+                        .write_error(&format!(
+                            "worker sent status for {}, expected {}",
+                            status.response_for,
+                            msg.id()
+                        ))
+                        .await
+                        .map_err(SupervisorError::TransportError)?;
+
+                    return Ok(false);
+                }
+                MessageOut::Checkpoint(_) => {
+                    self.transport
+                        .write_message(&out)
+                        .await
+                        .map_err(SupervisorError::TransportError)?;
+
+                    let reply = self
+                        .transport
+                        .read_message()
+                        .await
+                        .map_err(SupervisorError::TransportError)?;
+
+                    if !matches!(reply, MessageIn::Checkpoint(_)) {
+                        return Err(SupervisorError::UnexpectedMessage(reply));
+                    }
+
+                    if process.write(&reply).await.is_err() {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn crashed(&mut self) -> Result<(), SupervisorError<T::Error>> {
+        if let Some(mut process) = self.process.take() {
+            // The child may still be running if only its pipes broke
+            let _ = process.child.start_kill();
+            let status = process.child.wait().await;
+
+            let error = match status {
+                Ok(status) => format!("worker exited ({status}), restarting"),
+                Err(err) => format!("worker failed ({err}), restarting"),
+            };
+
+            self.transport
+                .write_error(&error)
+                .await
+                .map_err(SupervisorError::TransportError)?;
+        }
+
+        self.restarts += 1;
+
+        if self.restarts > self.options.max_restarts {
+            return Err(SupervisorError::RestartLimit {
+                restarts: self.restarts,
+            });
+        }
+
+        tokio::time::sleep(self.options.restart_delay).await;
+
+        Ok(())
+    }
+}
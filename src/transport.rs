@@ -6,6 +6,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use async_trait::async_trait;
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
 
+use super::json;
 use super::message::input::Message as MessageIn;
 use super::message::output::Message as MessageOut;
 
@@ -21,7 +22,7 @@ pub trait Transport {
 
 #[derive(Debug)]
 pub struct StdTransport {
-    buf: String,
+    buf: Vec<u8>,
     stdin: io::BufReader<io::Stdin>,
     stderr: io::BufWriter<io::Stderr>,
     stdout: std::io::BufWriter<std::io::Stdout>,
@@ -36,7 +37,7 @@ impl Default for StdTransport {
 impl StdTransport {
     pub fn new() -> Self {
         Self {
-            buf: String::with_capacity(2048),
+            buf: Vec::with_capacity(2048),
             stdin: io::BufReader::new(io::stdin()),
             stderr: io::BufWriter::new(io::stderr()),
             stdout: std::io::BufWriter::new(std::io::stdout()),
@@ -60,7 +61,7 @@ impl Transport for StdTransport {
 
     async fn write_message(&mut self, message: &MessageOut) -> Result<(), Self::Error> {
         self.stdout.write_all(b"\n")?;
-        simd_json::to_writer(&mut self.stdout, message)?;
+        json::to_writer(&mut self.stdout, message)?;
         self.stdout.write_all(b"\n")?;
 
         self.stdout.flush()?;
@@ -70,8 +71,9 @@ impl Transport for StdTransport {
 
     async fn read_message(&mut self) -> Result<MessageIn, Self::Error> {
         self.buf.clear();
-        self.stdin.read_line(&mut self.buf).await?;
-        let msg = match simd_json::from_slice(unsafe { self.buf.as_bytes_mut() }) {
+        self.stdin.read_until(b'\n', &mut self.buf).await?;
+
+        let msg = match json::from_slice(&mut self.buf) {
             Ok(msg) => msg,
             Err(err) => {
                 // Get current timestamp as milliseconds since UNIX_EPOCH
@@ -97,7 +99,8 @@ impl Transport for StdTransport {
                 };
 
                 // Write error, newline, then the message buffer
-                if let Err(e) = writeln!(file, "{}\n\n{}", err, self.buf) {
+                if let Err(e) = writeln!(file, "{}\n\n{}", err, String::from_utf8_lossy(&self.buf))
+                {
                     eprintln!("Failed to write to file {}: {}", file_path.display(), e);
                 }
 
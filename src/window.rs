@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::checkpoint::{CheckpointError, Checkpointer};
+use crate::message::input::{ProcessRecordsMessage, Record};
+use crate::sequence::ExtendedSequenceNumber;
+use crate::transport::Transport;
+
+#[derive(Debug, Clone, Copy)]
+pub enum WindowKind {
+    Tumbling { size: Duration },
+    Sliding { size: Duration, slide: Duration },
+}
+
+/// Time range `[start_ms, end_ms)` in milliseconds since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Window {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[derive(Debug)]
+struct OpenWindow<A> {
+    aggregate: A,
+    records: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct PendingRecord {
+    sequence_number: ExtendedSequenceNumber,
+    open_windows: usize,
+}
+
+type EventTime = Box<dyn Fn(&Record) -> Option<u64> + Send + Sync>;
+
+/// Aggregates records into time windows across batches.
+///
+/// Windows close once the watermark passes their end. The watermark is the
+/// position of the shard (`now - millisBehindLatest`) minus the allowed
+/// lateness. With a custom event time, or if the daemon does not report the
+/// lag, the highest event time seen is used instead. Only records whose
+/// windows are all closed are checkpointed, so a restart replays every record
+/// of a window that was not emitted yet.
+pub struct Windows<A, F> {
+    kind: WindowKind,
+    allowed_lateness: Duration,
+    event_time: EventTime,
+    custom_event_time: bool,
+    fold: F,
+    open: BTreeMap<Window, OpenWindow<A>>,
+    pending: VecDeque<PendingRecord>,
+    pending_offset: u64,
+    watermark_ms: u64,
+    max_event_time_ms: u64,
+    late_records: u64,
+    completed: Option<ExtendedSequenceNumber>,
+    checkpointed: Option<ExtendedSequenceNumber>,
+}
+
+impl<A, F> Windows<A, F>
+where
+    A: Default,
+    F: FnMut(&mut A, &Record),
+{
+    /// Uses `approximateArrivalTimestamp` as event time.
+    pub fn new(kind: WindowKind, fold: F) -> Self {
+        Self {
+            kind,
+            allowed_lateness: Duration::ZERO,
+            event_time: Box::new(|record| record.approximate_arrival_timestamp_ms),
+            custom_event_time: false,
+            fold,
+            open: BTreeMap::new(),
+            pending: VecDeque::new(),
+            pending_offset: 0,
+            watermark_ms: 0,
+            max_event_time_ms: 0,
+            late_records: 0,
+            completed: None,
+            checkpointed: None,
+        }
+    }
+
+    /// Extracts the event time (milliseconds since the UNIX epoch) from a record.
+    pub fn with_event_time(
+        mut self,
+        event_time: impl Fn(&Record) -> Option<u64> + Send + Sync + 'static,
+    ) -> Self {
+        self.event_time = Box::new(event_time);
+        self.custom_event_time = true;
+        self
+    }
+
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
+    pub fn watermark_ms(&self) -> u64 {
+        self.watermark_ms
+    }
+
+    /// Number of records left out of at least one of their windows because it
+    /// had already closed.
+    pub fn late_records(&self) -> u64 {
+        self.late_records
+    }
+
+    /// Adds the records of a batch and emits all windows closed by it.
+    ///
+    /// Records without event time are skipped, as are records for windows
+    /// which already closed (counted in [`Windows::late_records`]).
+    pub fn push(&mut self, msg: &ProcessRecordsMessage, emit: impl FnMut(Window, A)) {
+        for record in &msg.records {
+            let index = self.pending_offset + self.pending.len() as u64;
+            let mut open_windows = 0;
+
+            if let Some(event_time) = (self.event_time)(record) {
+                self.max_event_time_ms = self.max_event_time_ms.max(event_time);
+                let mut late = false;
+
+                for window in self.windows_of(event_time) {
+                    if window.end_ms <= self.watermark_ms {
+                        late = true;
+                        continue;
+                    }
+
+                    let open = self.open.entry(window).or_insert_with(|| OpenWindow {
+                        aggregate: A::default(),
+                        records: vec![],
+                    });
+
+                    (self.fold)(&mut open.aggregate, record);
+                    open.records.push(index);
+                    open_windows += 1;
+                }
+
+                if late {
+                    self.late_records += 1;
+                }
+            }
+
+            self.pending.push_back(PendingRecord {
+                sequence_number: record.extended_sequence_number(),
+                open_windows,
+            });
+        }
+
+        let lateness = self.allowed_lateness.as_millis() as u64;
+
+        // The lag only relates to the arrival timestamps of the shard
+        let position = match msg.millis_behind_latest {
+            Some(behind) if !self.custom_event_time => now_ms().saturating_sub(behind),
+            _ => self.max_event_time_ms,
+        };
+
+        self.advance(position.saturating_sub(lateness), emit);
+    }
+
+    /// Emits all open windows, e.g. before shutting down.
+    pub fn flush(&mut self, emit: impl FnMut(Window, A)) {
+        self.advance(u64::MAX, emit);
+    }
+
+    /// Highest sequence number up to which all records belong to closed windows.
+    pub fn checkpoint_sequence_number(&self) -> Option<&ExtendedSequenceNumber> {
+        self.completed.as_ref()
+    }
+
+    /// Checkpoints at [`Windows::checkpoint_sequence_number`] if it advanced
+    /// since the last call.
+    pub async fn checkpoint<T: Transport>(
+        &mut self,
+        checkpointer: &mut Checkpointer<'_, T>,
+    ) -> Result<(), CheckpointError<T::Error>> {
+        let Some(completed) = &self.completed else {
+            return Ok(());
+        };
+
+        if self.checkpointed.as_ref() == Some(completed) {
+            return Ok(());
+        }
+
+        checkpointer
+            .checkpoint(
+                Some(completed.sequence_number.clone()),
+                completed.sub_sequence_number,
+            )
+            .await?;
+
+        self.checkpointed = Some(completed.clone());
+
+        Ok(())
+    }
+
+    fn windows_of(&self, event_time: u64) -> Vec<Window> {
+        let (size, slide) = match self.kind {
+            WindowKind::Tumbling { size } => (size, size),
+            WindowKind::Sliding { size, slide } => (size, slide),
+        };
+
+        let size = (size.as_millis() as u64).max(1);
+        let slide = (slide.as_millis() as u64).clamp(1, size);
+
+        // Latest window containing the event, then step back while it still fits
+        let mut start = event_time - event_time % slide;
+        let mut windows = vec![];
+
+        loop {
+            windows.push(Window {
+                start_ms: start,
+                end_ms: start.saturating_add(size),
+            });
+
+            match start.checked_sub(slide) {
+                Some(prev) if prev.saturating_add(size) > event_time => start = prev,
+                _ => break,
+            }
+        }
+
+        windows.reverse();
+        windows
+    }
+
+    fn advance(&mut self, watermark_ms: u64, mut emit: impl FnMut(Window, A)) {
+        self.watermark_ms = self.watermark_ms.max(watermark_ms);
+
+        let closed = self
+            .open
+            .keys()
+            .filter(|window| window.end_ms <= self.watermark_ms)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for window in closed {
+            let Some(open) = self.open.remove(&window) else {
+                continue;
+            };
+
+            for index in open.records {
+                let pending = &mut self.pending[(index - self.pending_offset) as usize];
+                pending.open_windows -= 1;
+            }
+
+            emit(window, open.aggregate);
+        }
+
+        while self
+            .pending
+            .front()
+            .is_some_and(|pending| pending.open_windows == 0)
+        {
+            if let Some(pending) = self.pending.pop_front() {
+                self.pending_offset += 1;
+                self.completed = Some(pending.sequence_number);
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
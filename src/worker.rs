@@ -0,0 +1,75 @@
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+use crate::json;
+use crate::message::input::Message as MessageIn;
+use crate::message::output::Message as MessageOut;
+
+/// Worker child process speaking the protocol over its stdin and stdout.
+#[derive(Debug)]
+pub(crate) struct WorkerProcess {
+    pub(crate) child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    buf: String,
+}
+
+impl WorkerProcess {
+    pub(crate) fn spawn(command: &mut Command) -> std::io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or(std::io::ErrorKind::BrokenPipe)?;
+        let stdout = child.stdout.take().ok_or(std::io::ErrorKind::BrokenPipe)?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            buf: String::with_capacity(2048),
+        })
+    }
+
+    pub(crate) async fn write(&mut self, msg: &MessageIn) -> std::io::Result<()> {
+        let mut line = json::to_vec(msg).map_err(invalid_data)?;
+        line.push(b'\n');
+
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await
+    }
+
+    /// Reads the next message, skipping the empty lines workers emit around
+    /// them. Returns `None` once the worker closed its stdout.
+    pub(crate) async fn read(&mut self) -> std::io::Result<Option<MessageOut>> {
+        loop {
+            self.buf.clear();
+
+            if self.stdout.read_line(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+
+            if self.buf.trim().is_empty() {
+                continue;
+            }
+
+            let mut line = self.buf.as_bytes().to_vec();
+
+            return json::from_slice(&mut line).map(Some).map_err(invalid_data);
+        }
+    }
+
+    #[cfg(feature = "local")]
+    pub(crate) async fn wait(mut self) -> std::io::Result<std::process::ExitStatus> {
+        drop(self.stdin);
+        self.child.wait().await
+    }
+}
+
+fn invalid_data(err: json::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+}